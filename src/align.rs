@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+use conllx::graph::Sentence;
+use failure::{format_err, Error};
+
+use crate::conllu::Reader;
+
+/// Validation and prediction sentences paired by `sent_id`.
+pub struct Alignment {
+    /// Validation sentences with a matching prediction, in validation
+    /// order.
+    pub val: Vec<Sentence>,
+
+    /// The predictions matching `val`.
+    pub pred: Vec<Sentence>,
+
+    /// The ids of `val`.
+    pub ids: Vec<String>,
+
+    /// Ids of validation sentences without a prediction.
+    pub unmatched_val: Vec<String>,
+
+    /// Ids of predicted sentences without a validation sentence, in
+    /// prediction order.
+    pub unmatched_pred: Vec<String>,
+}
+
+/// Pair validation and prediction sentences by their `sent_id` comments.
+///
+/// Every sentence must have a `sent_id` that is unique within its file.
+pub fn align_by_sent_id<R, S>(
+    val_reader: &mut Reader<R>,
+    pred_reader: &mut Reader<S>,
+) -> Result<Alignment, Error>
+where
+    R: BufRead,
+    S: BufRead,
+{
+    let val_sentences = read_with_ids(val_reader, "Validation")?;
+    let pred_sentences = read_with_ids(pred_reader, "Prediction")?;
+    let mut pred_order = Vec::with_capacity(pred_sentences.len());
+    let mut pred_by_id = HashMap::with_capacity(pred_sentences.len());
+    for (id, sentence) in pred_sentences {
+        pred_order.push(id.clone());
+        pred_by_id.insert(id, sentence);
+    }
+
+    let mut alignment = Alignment {
+        val: Vec::new(),
+        pred: Vec::new(),
+        ids: Vec::new(),
+        unmatched_val: Vec::new(),
+        unmatched_pred: Vec::new(),
+    };
+    for (id, val_sentence) in val_sentences {
+        match pred_by_id.remove(&id) {
+            Some(pred_sentence) => {
+                alignment.val.push(val_sentence);
+                alignment.pred.push(pred_sentence);
+                alignment.ids.push(id);
+            }
+            None => alignment.unmatched_val.push(id),
+        }
+    }
+    alignment.unmatched_pred = pred_order
+        .into_iter()
+        .filter(|id| pred_by_id.contains_key(id))
+        .collect();
+
+    Ok(alignment)
+}
+
+fn read_with_ids<R>(reader: &mut Reader<R>, side: &str) -> Result<Vec<(String, Sentence)>, Error>
+where
+    R: BufRead,
+{
+    let mut sentences = Vec::new();
+    let mut ids = HashSet::new();
+    while let Some((id, sentence)) = reader.read_sentence_with_id()? {
+        let id = id.ok_or_else(|| format_err!("{} sentence {} has no sent_id", side, sentences.len()))?;
+        if !ids.insert(id.clone()) {
+            return Err(format_err!("{} sent_id occurs more than once: {}", side, id));
+        }
+        sentences.push((id, sentence));
+    }
+    Ok(sentences)
+}
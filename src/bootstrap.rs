@@ -0,0 +1,173 @@
+use crate::rng::SplitMix64;
+use crate::{ratio, AttachmentCounts};
+
+/// A confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub lower: f32,
+    pub upper: f32,
+}
+
+/// Bootstrap confidence intervals of the attachment scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BootstrapCi {
+    pub uas: Interval,
+    pub las: Interval,
+}
+
+/// Estimate 95% confidence intervals of UAS and LAS.
+///
+/// Sentences are resampled with replacement `n_samples` times. The
+/// intervals are given by the 2.5th and 97.5th percentiles of the
+/// scores of the resampled corpora.
+pub fn bootstrap(scores: &[AttachmentCounts], n_samples: usize, seed: u64) -> BootstrapCi {
+    let mut rng = SplitMix64::new(seed);
+    let mut uas = Vec::with_capacity(n_samples);
+    let mut las = Vec::with_capacity(n_samples);
+    for _ in 0..n_samples {
+        let mut sample = AttachmentCounts::default();
+        for _ in 0..scores.len() {
+            sample += scores[rng.gen_index(scores.len())];
+        }
+        uas.push(sample.uas());
+        las.push(sample.las());
+    }
+
+    BootstrapCi {
+        uas: percentile_interval(uas),
+        las: percentile_interval(las),
+    }
+}
+
+/// Get the interval between the 2.5th and 97.5th percentiles.
+///
+/// The interval of an empty sample is `[0, 0]`.
+fn percentile_interval(mut values: Vec<f32>) -> Interval {
+    if values.is_empty() {
+        return Interval { lower: 0., upper: 0. };
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let last = (values.len() - 1) as f32;
+    Interval {
+        lower: values[(0.025 * last).round() as usize],
+        upper: values[(0.975 * last).round() as usize],
+    }
+}
+
+/// Result of a paired significance test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PairedTest {
+    /// Corpus LAS of the second system minus the first system's.
+    pub las_delta: f32,
+
+    /// Mean of the per-sentence LAS differences.
+    ///
+    /// Sentences without scored tokens are ignored, the mean is 0 if no
+    /// sentence has scored tokens.
+    pub mean_sentence_delta: f32,
+
+    /// p-value of the LAS difference.
+    pub p_value: f32,
+}
+
+/// Paired bootstrap test of the LAS difference between two systems.
+///
+/// `a` and `b` hold the per-sentence counts of both systems on the same
+/// sentences. Sentences are resampled with replacement `n_samples` times.
+/// Since the resampled differences are centered around the observed
+/// difference `d`, the p-value is the fraction of samples whose
+/// difference exceeds `2d` (Berg-Kirkpatrick et al., 2012), i.e. it
+/// tests whether the system with the higher LAS is significantly better.
+pub fn paired_bootstrap(a: &[AttachmentCounts], b: &[AttachmentCounts], n_samples: usize, seed: u64) -> PairedTest {
+    assert_eq!(a.len(), b.len(), "Systems were scored on different numbers of sentences");
+
+    let total = |scores: &[AttachmentCounts]| {
+        scores.iter().fold(AttachmentCounts::default(), |mut acc, &score| {
+            acc += score;
+            acc
+        })
+    };
+    let las_delta = total(b).las() - total(a).las();
+
+    let sentence_deltas = a
+        .iter()
+        .zip(b)
+        .filter(|(a, _)| a.total != 0)
+        .map(|(a, b)| b.las() - a.las())
+        .collect::<Vec<_>>();
+    let mean_sentence_delta = if sentence_deltas.is_empty() {
+        0.
+    } else {
+        sentence_deltas.iter().sum::<f32>() / sentence_deltas.len() as f32
+    };
+
+    let mut rng = SplitMix64::new(seed);
+    let mut exceeding = 0;
+    for _ in 0..n_samples {
+        let mut sample_a = AttachmentCounts::default();
+        let mut sample_b = AttachmentCounts::default();
+        for _ in 0..a.len() {
+            let idx = rng.gen_index(a.len());
+            sample_a += a[idx];
+            sample_b += b[idx];
+        }
+        let sample_delta = sample_b.las() - sample_a.las();
+        if sample_delta.abs() > 2. * las_delta.abs() && sample_delta.signum() == las_delta.signum() {
+            exceeding += 1;
+        }
+    }
+
+    PairedTest {
+        las_delta,
+        mean_sentence_delta,
+        p_value: ratio(exceeding, n_samples),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bootstrap, paired_bootstrap, Interval};
+    use crate::AttachmentCounts;
+
+    fn counts(total: usize, correct_head: usize, correct_head_label: usize) -> AttachmentCounts {
+        AttachmentCounts {
+            total,
+            correct_head,
+            correct_head_label,
+        }
+    }
+
+    #[test]
+    fn bootstrap_of_identical_sentences() {
+        let ci = bootstrap(&[counts(4, 2, 1); 3], 100, 42);
+        assert_eq!(ci.uas, Interval { lower: 0.5, upper: 0.5 });
+        assert_eq!(ci.las, Interval { lower: 0.25, upper: 0.25 });
+    }
+
+    #[test]
+    fn bootstrap_without_sentences() {
+        let ci = bootstrap(&[], 10, 42);
+        assert_eq!(ci.uas, Interval { lower: 0., upper: 0. });
+        assert_eq!(ci.las, Interval { lower: 0., upper: 0. });
+    }
+
+    #[test]
+    fn paired_bootstrap_deltas() {
+        let a = [counts(2, 2, 1), counts(4, 4, 2), counts(0, 0, 0)];
+        let b = [counts(2, 2, 2), counts(4, 4, 4), counts(0, 0, 0)];
+        let test = paired_bootstrap(&a, &b, 100, 42);
+        assert_eq!(test.las_delta, 0.5);
+        // The empty sentence is not averaged.
+        assert_eq!(test.mean_sentence_delta, 0.5);
+        // Every sample has the observed difference, never twice of it.
+        assert_eq!(test.p_value, 0.);
+    }
+
+    #[test]
+    fn paired_bootstrap_without_scored_tokens() {
+        let test = paired_bootstrap(&[counts(0, 0, 0)], &[counts(0, 0, 0)], 10, 42);
+        assert_eq!(test.las_delta, 0.);
+        assert_eq!(test.mean_sentence_delta, 0.);
+        assert_eq!(test.p_value, 0.);
+    }
+}
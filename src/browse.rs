@@ -0,0 +1,281 @@
+//! Interactive browsing of sentences with attachment errors.
+//!
+//! The browser reads commands line by line, so it works in any terminal
+//! without a terminal UI library. Every sentence with an error is shown
+//! as the gold and the predicted tree side by side, tokens with an error
+//! are marked with `*`.
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use conllx::graph::Sentence;
+use conllx::io::ReadSentence;
+use failure::{format_err, Error};
+
+use crate::eval::{check_sentence_pair, score_token, SentenceSelection, TokenDecision};
+use crate::EvalOptions;
+
+/// The kind of an attachment error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The predicted head is wrong.
+    Head,
+
+    /// The predicted head is correct, the relation is wrong.
+    Label,
+}
+
+/// A sentence with attachment errors.
+pub struct ErrorSentence {
+    /// Index of the sentence in the input.
+    pub idx: usize,
+
+    pub val: Sentence,
+
+    pub pred: Sentence,
+
+    /// Tokens with an error, with their error kind and gold relation.
+    pub errors: Vec<(usize, ErrorKind, String)>,
+}
+
+impl ErrorSentence {
+    fn matches(&self, rel: Option<&str>, kind: Option<ErrorKind>) -> bool {
+        self.errors.iter().any(|(_, error_kind, error_rel)| {
+            rel.map(|rel| rel == error_rel).unwrap_or(true) && kind.map(|kind| kind == *error_kind).unwrap_or(true)
+        })
+    }
+}
+
+/// Read the sentence pairs with attachment errors.
+///
+/// Tokens and sentences are selected and scored as in `evaluate_with`,
+/// so a token has an error if it would count as an error in the
+/// attachment scores. Error relations are normalized as in the scores.
+pub fn read_error_sentences(
+    mut val_reader: impl ReadSentence,
+    mut pred_reader: impl ReadSentence,
+    options: &EvalOptions,
+) -> Result<Vec<ErrorSentence>, Error> {
+    let mut sentences = Vec::new();
+    let mut selection = SentenceSelection::new(options);
+    let mut n_read = 0;
+    loop {
+        if selection.done(n_read) {
+            break;
+        }
+        let idx = n_read;
+        let (val, pred) = match (val_reader.read_sentence()?, pred_reader.read_sentence()?) {
+            (Some(val), Some(pred)) => (val, pred),
+            (None, None) => break,
+            _ => return Err(format_err!("Validation and prediction have a different number of sentences")),
+        };
+        n_read += 1;
+        if selection.skip(idx) || !selection.sample() {
+            continue;
+        }
+        if check_sentence_pair(idx, &val, &pred, options)?.is_some() {
+            continue;
+        }
+        let mut errors = Vec::new();
+        for token in 1..val.len() {
+            let val_token = val[token].token().expect("Non-root node without token");
+            let pred_token = pred[token].token().expect("Non-root node without token");
+            let val_triple = val.dep_graph().head(token);
+            let pred_triple = pred.dep_graph().head(token);
+            let score = match score_token(idx, token, val_token, pred_token, val_triple.as_ref(), pred_triple.as_ref(), options)? {
+                TokenDecision::Scored(score) if !score.excluded => score,
+                _ => continue,
+            };
+            let kind = if !score.head_correct {
+                ErrorKind::Head
+            } else if !score.rel_correct {
+                ErrorKind::Label
+            } else {
+                continue;
+            };
+            errors.push((token, kind, score.val_rel.to_owned()));
+        }
+        if !errors.is_empty() {
+            sentences.push(ErrorSentence { idx, val, pred, errors });
+        }
+    }
+    selection.check_range(n_read)?;
+    Ok(sentences)
+}
+
+/// Render a sentence as an indented tree.
+///
+/// Every line holds a token as `form (id, relation)`, dependents are
+/// indented below their head. Tokens in `marked` are prefixed with `*`.
+/// Tokens that cannot be reached from the root, e.g. because of a cycle
+/// or a missing head, are listed at the end with a `?` prefix.
+pub fn render_tree(sentence: &Sentence, marked: &HashSet<usize>) -> Vec<String> {
+    let graph = sentence.dep_graph();
+    let mut dependents = vec![Vec::new(); sentence.len()];
+    for token in 1..sentence.len() {
+        if let Some(triple) = graph.head(token) {
+            if triple.head() < sentence.len() {
+                dependents[triple.head()].push(token);
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = dependents[0].iter().rev().map(|&token| (token, 0)).collect::<Vec<_>>();
+    while let Some((token, depth)) = stack.pop() {
+        if !visited.insert(token) {
+            continue;
+        }
+        lines.push(format!("{}{}", "  ".repeat(depth), token_label(sentence, token, marked)));
+        stack.extend(dependents[token].iter().rev().map(|&dependent| (dependent, depth + 1)));
+    }
+    for token in 1..sentence.len() {
+        if !visited.contains(&token) {
+            lines.push(format!("? {}", token_label(sentence, token, marked)));
+        }
+    }
+    lines
+}
+
+fn token_label(sentence: &Sentence, token: usize, marked: &HashSet<usize>) -> String {
+    let form = sentence[token].token().map(|t| t.form()).unwrap_or("_");
+    let rel = sentence
+        .dep_graph()
+        .head(token)
+        .and_then(|triple| triple.relation().map(ToOwned::to_owned))
+        .unwrap_or_else(|| "_".to_owned());
+    let mark = if marked.contains(&token) { "*" } else { "" };
+    format!("{}{} ({}, {})", mark, form, token, rel)
+}
+
+/// Write the gold and predicted tree of a sentence side by side.
+pub fn write_side_by_side(mut w: impl Write, sentence: &ErrorSentence) -> Result<(), Error> {
+    let marked = sentence.errors.iter().map(|&(token, _, _)| token).collect::<HashSet<_>>();
+    let gold = render_tree(&sentence.val, &marked);
+    let pred = render_tree(&sentence.pred, &marked);
+    let width = gold.iter().map(|line| line.chars().count()).max().unwrap_or(0).max("gold".len());
+    writeln!(w, "{:width$} | predicted", "gold", width = width)?;
+    for idx in 0..gold.len().max(pred.len()) {
+        writeln!(
+            w,
+            "{:width$} | {}",
+            gold.get(idx).map(String::as_str).unwrap_or(""),
+            pred.get(idx).map(String::as_str).unwrap_or(""),
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+static HELP: &str = "commands: n (next), p (previous), r REL (filter by gold relation), \
+                     t head|label (filter by error type), r / t (clear filter), q (quit)";
+
+/// Browse sentences with errors, reading commands from `input`.
+///
+/// An empty line shows the next sentence.
+pub fn browse(sentences: &[ErrorSentence], mut input: impl BufRead, mut output: impl Write) -> Result<(), Error> {
+    let mut rel: Option<String> = None;
+    let mut kind: Option<ErrorKind> = None;
+    let mut current: Option<usize> = None;
+    writeln!(output, "{} sentences with errors, {}", sentences.len(), HELP)?;
+    let mut line = String::new();
+    let mut command = "n".to_owned();
+    loop {
+        let mut parts = command.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("q"), _) => return Ok(()),
+            (None, _) | (Some("n"), _) | (Some("p"), _) => {
+                let matching = |&idx: &usize| sentences[idx].matches(rel.as_deref(), kind);
+                let next = if command.trim() == "p" {
+                    (0..current.unwrap_or(0)).rev().find(matching)
+                } else {
+                    (current.map(|idx| idx + 1).unwrap_or(0)..sentences.len()).find(matching)
+                };
+                match next {
+                    Some(idx) => {
+                        current = Some(idx);
+                        let sentence = &sentences[idx];
+                        writeln!(output, "sentence {} ({} errors)", sentence.idx, sentence.errors.len())?;
+                        write_side_by_side(&mut output, sentence)?;
+                    }
+                    None => writeln!(output, "no further matching sentence")?,
+                }
+            }
+            (Some("r"), filter) => {
+                rel = filter.map(ToOwned::to_owned);
+                current = None;
+                writeln!(output, "relation filter: {}", rel.as_deref().unwrap_or("none"))?;
+            }
+            (Some("t"), filter) => {
+                kind = match filter {
+                    Some("head") => Some(ErrorKind::Head),
+                    Some("label") => Some(ErrorKind::Label),
+                    _ => None,
+                };
+                current = None;
+                writeln!(output, "error type filter: {}", filter.filter(|_| kind.is_some()).unwrap_or("none"))?;
+            }
+            _ => writeln!(output, "{}", HELP)?,
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        command = line.trim().to_owned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_error_sentences, ErrorKind};
+    use crate::conllu::Reader;
+    use crate::EvalOptions;
+
+    static GOLD: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tnsubj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t3\tpunct\t_\t_
+
+1\tCats\tcat\tNOUN\tNNS\t_\t2\tnsubj\t_\t_
+2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    /// The relation of `dog` and the head of `.` are wrong.
+    static PRED: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tobj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t2\tpunct\t_\t_
+
+1\tCats\tcat\tNOUN\tNNS\t_\t2\tnsubj\t_\t_
+2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    type TokenErrors = Vec<(usize, ErrorKind, String)>;
+
+    fn errors(options: &EvalOptions) -> Vec<(usize, TokenErrors)> {
+        read_error_sentences(Reader::new(GOLD.as_bytes()), Reader::new(PRED.as_bytes()), options)
+            .unwrap()
+            .into_iter()
+            .map(|sentence| (sentence.idx, sentence.errors))
+            .collect()
+    }
+
+    #[test]
+    fn errors_follow_the_options() {
+        assert_eq!(
+            errors(&EvalOptions::default()),
+            vec![(0, vec![(2, ErrorKind::Label, "nsubj".to_owned()), (4, ErrorKind::Head, "punct".to_owned())])]
+        );
+        let options = EvalOptions {
+            no_punct: true,
+            ignore_rels: vec!["nsubj".to_owned()].into_iter().collect(),
+            ..EvalOptions::default()
+        };
+        assert!(errors(&options).is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use failure::{format_err, Error};
+
+/// Buckets of positive integers.
+///
+/// Buckets are defined by their inclusive upper bounds. The bounds
+/// `1,2,3,5,10` define the buckets `1`, `2`, `3`, `4-5`, `6-10` and
+/// `11+`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Buckets {
+    bounds: Vec<usize>,
+}
+
+impl Buckets {
+    /// Construct buckets from strictly increasing upper bounds.
+    pub fn new(bounds: Vec<usize>) -> Result<Self, Error> {
+        if bounds.is_empty() {
+            return Err(format_err!("At least one bucket bound is required"));
+        }
+        if bounds.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(format_err!("Bucket bounds must be strictly increasing"));
+        }
+        Ok(Buckets { bounds })
+    }
+
+    /// Parse comma-separated upper bounds, e.g. `1,2,3,5,10`.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let bounds = s
+            .split(',')
+            .map(|b| {
+                b.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format_err!("Cannot parse bucket bound: {}", b))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Buckets::new(bounds)
+    }
+
+    /// Get the number of buckets.
+    pub fn n_buckets(&self) -> usize {
+        self.bounds.len() + 1
+    }
+
+    /// Get the index of the bucket containing `value`.
+    pub fn index(&self, value: usize) -> usize {
+        self.bounds
+            .iter()
+            .position(|&upper| value <= upper)
+            .unwrap_or(self.bounds.len())
+    }
+
+    /// Get the label of the bucket containing `value`.
+    pub fn label(&self, value: usize) -> String {
+        self.index_label(self.index(value))
+    }
+
+    /// Get the labels of all buckets in ascending order.
+    pub fn labels(&self) -> Vec<String> {
+        (0..self.n_buckets()).map(|idx| self.index_label(idx)).collect()
+    }
+
+    fn index_label(&self, idx: usize) -> String {
+        let lower = if idx == 0 { 1 } else { self.bounds[idx - 1] + 1 };
+        match self.bounds.get(idx) {
+            Some(&upper) if lower >= upper => upper.to_string(),
+            Some(&upper) => format!("{}-{}", lower, upper),
+            None => format!("{}+", lower),
+        }
+    }
+}
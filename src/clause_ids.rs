@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use conllx::token::Token;
+use failure::{format_err, Error};
+
+use crate::feats::parse_features;
+
+/// Token feature that holds the clause id of a predicted token.
+pub static CLAUSE_ID_FEATURE: &str = "clause_id";
+
+/// Relation of predicted tokens without (mapped) clause id.
+pub static NO_CLAUSE_RELATION: &str = "_";
+
+/// Mapping from clause ids to relations.
+///
+/// The relation of a predicted token is derived from the value of its
+/// `clause_id` feature, e.g. `clause_id=3` in CoNLL-U or `clause_id:3`
+/// in CoNLL-X. The mapping is read from a file with one
+/// `CLAUSE_ID<TAB>RELATION` pair per line (columns are tab-separated):
+///
+/// ```text
+/// 1   root
+/// 2   ccomp
+/// 3   advcl
+/// ```
+///
+/// Tokens without the feature, or with a clause id that is not listed,
+/// get the relation `_`.
+#[derive(Clone, Debug)]
+pub struct ClauseRelations {
+    relations: HashMap<String, String>,
+}
+
+impl ClauseRelations {
+    /// Read the mapping from a file.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut relations = HashMap::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let mut parts = line.split('\t');
+            let (clause_id, relation) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(clause_id), Some(relation), None) => (clause_id.trim(), relation.trim()),
+                _ => return Err(format_err!("{}:{}: expected CLAUSE_ID<TAB>RELATION", path, line_no + 1)),
+            };
+            if relations.insert(clause_id.to_owned(), relation.to_owned()).is_some() {
+                return Err(format_err!("{}:{}: clause id is mapped more than once", path, line_no + 1));
+            }
+        }
+        Ok(ClauseRelations { relations })
+    }
+
+    /// The relation derived from the clause id of a predicted token.
+    pub fn relation(&self, token: &Token) -> &str {
+        token
+            .features()
+            .and_then(|features| parse_features(features.as_str()).get(CLAUSE_ID_FEATURE).copied())
+            .and_then(|clause_id| self.relations.get(clause_id))
+            .map(String::as_str)
+            .unwrap_or(NO_CLAUSE_RELATION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::token::{Features, Token};
+
+    use super::ClauseRelations;
+
+    fn token(features: Option<&str>) -> Token {
+        let mut token = Token::new("barks");
+        token.set_features(features.map(Features::from_string));
+        token
+    }
+
+    #[test]
+    fn clause_ids_map_to_relations() {
+        let relations = ClauseRelations {
+            relations: vec![("1".to_owned(), "root".to_owned()), ("2".to_owned(), "ccomp".to_owned())]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(relations.relation(&token(Some("clause_id=2"))), "ccomp");
+        assert_eq!(relations.relation(&token(Some("Number=Sing|clause_id:1"))), "root");
+        assert_eq!(relations.relation(&token(Some("clause_id=3"))), "_");
+        assert_eq!(relations.relation(&token(Some("Number=Sing"))), "_");
+        assert_eq!(relations.relation(&token(None)), "_");
+    }
+}
@@ -0,0 +1,780 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::io::Write;
+
+use failure::Error;
+use itertools::Itertools;
+
+use crate::metrics::{f_beta, ratio, Rounding, DEFAULT_PRECISION};
+
+pub struct Confusion<V> {
+    confusion: Vec<Vec<usize>>,
+    numberer: Numberer<V>,
+    name: String,
+    rounding: Rounding,
+    precision: usize,
+    aggregate_excluded: Option<V>,
+}
+
+impl<V> Confusion<V> where V: Clone + Hash + Eq {
+    pub fn new(name: impl Into<String>) -> Self {
+        Confusion {
+            confusion: Vec::new(),
+            numberer: Numberer::new(),
+            name: name.into(),
+            rounding: Rounding::default(),
+            precision: DEFAULT_PRECISION,
+            aggregate_excluded: None,
+        }
+    }
+
+    /// Set the number of decimal places of formatted accuracies and
+    /// precisions.
+    pub fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
+    }
+
+    /// Set the rounding mode for formatted accuracies and precisions.
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+    }
+
+    /// Exclude a value from the aggregate scores of `write` and
+    /// `write_accuracies`, e.g. the root.
+    ///
+    /// Its row does not count towards the accuracy and the precisions,
+    /// and no accuracy or precision is written for it. The cells of the
+    /// matrix are unchanged.
+    pub fn exclude_from_aggregates(&mut self, val: V) {
+        self.aggregate_excluded = Some(val);
+    }
+
+    /// Get the index of the value excluded from the aggregate scores.
+    fn aggregate_excluded_idx(&self) -> Option<usize> {
+        self.aggregate_excluded
+            .as_ref()
+            .and_then(|val| self.numberer.get_number(val))
+    }
+
+    /// Precision of the value with index `idx`, ignoring the row of the
+    /// value excluded from the aggregates.
+    fn aggregate_precision(&self, idx: usize) -> f32 {
+        match self.aggregate_excluded_idx() {
+            Some(excluded) => {
+                let predicted = self
+                    .confusion
+                    .iter()
+                    .enumerate()
+                    .filter(|&(target_idx, _)| target_idx != excluded)
+                    .map(|(_, row)| row[idx])
+                    .sum::<usize>();
+                ratio(self.confusion[idx][idx], predicted)
+            }
+            None => self.precision(idx),
+        }
+    }
+
+    /// Get the indices of the values with a non-zero support that are not
+    /// excluded from the aggregates.
+    fn supported_indices(&self) -> Vec<usize> {
+        let excluded = self.aggregate_excluded_idx();
+        (0..self.confusion.len())
+            .filter(|&idx| Some(idx) != excluded && self.confusion[idx].iter().sum::<usize>() != 0)
+            .collect()
+    }
+
+    /// Macro-averaged precision.
+    ///
+    /// The average of the precisions of all values that occur as target.
+    /// Values that were only predicted are not averaged. Returns 0 for an
+    /// empty matrix.
+    pub fn macro_precision(&self) -> f32 {
+        let indices = self.supported_indices();
+        let sum = indices.iter().map(|&idx| self.aggregate_precision(idx)).sum::<f32>();
+        if indices.is_empty() {
+            0.
+        } else {
+            sum / indices.len() as f32
+        }
+    }
+
+    /// Macro-averaged recall.
+    ///
+    /// The average of the recalls of all values that occur as target.
+    /// Values that were only predicted have no recall and are not
+    /// averaged. Returns 0 for an empty matrix.
+    pub fn macro_recall(&self) -> f32 {
+        let indices = self.supported_indices();
+        let sum = indices.iter().map(|&idx| self.recall(idx)).sum::<f32>();
+        if indices.is_empty() {
+            0.
+        } else {
+            sum / indices.len() as f32
+        }
+    }
+
+    pub fn insert<S>(&mut self, target: S, prediction: S) where S: Into<V> {
+        let target_idx = self.numberer.number(target);
+        let pred_idx = self.numberer.number(prediction);
+        self.grow();
+        self.confusion[target_idx][pred_idx] += 1;
+    }
+
+    /// Add the counts of another confusion matrix.
+    ///
+    /// Values that are not yet known are numbered in the order of
+    /// `other`, so merging the matrices of consecutive parts of the
+    /// data gives the same matrix as inserting all data into one.
+    pub fn merge(&mut self, other: Confusion<V>) {
+        let mapping = other
+            .numberer
+            .idx2val
+            .into_iter()
+            .map(|val| self.numberer.number(val))
+            .collect::<Vec<_>>();
+        self.grow();
+        for (target_idx, row) in other.confusion.into_iter().enumerate() {
+            for (pred_idx, count) in row.into_iter().enumerate() {
+                self.confusion[mapping[target_idx]][mapping[pred_idx]] += count;
+            }
+        }
+    }
+
+    /// Renumber the values in the order given by `compare`.
+    ///
+    /// The rows and columns of the matrix are reordered accordingly, so
+    /// that the outputs do not depend on the order in which the values
+    /// were first inserted.
+    pub fn sort_values_by(&mut self, mut compare: impl FnMut(&V, &V) -> Ordering) {
+        let mut order = (0..self.numberer.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| compare(&self.numberer.idx2val[a], &self.numberer.idx2val[b]));
+        let confusion = order
+            .iter()
+            .map(|&target_idx| order.iter().map(|&pred_idx| self.confusion[target_idx][pred_idx]).collect())
+            .collect();
+        let mut numberer = Numberer::new();
+        for &idx in &order {
+            numberer.number(self.numberer.idx2val[idx].clone());
+        }
+        self.confusion = confusion;
+        self.numberer = numberer;
+    }
+
+    /// Remove the values that occur less than `min_count` times both as
+    /// target and as prediction.
+    ///
+    /// The cells of removed values are dropped, so the remaining counts
+    /// no longer sum to the number of tokens.
+    pub fn prune(&mut self, min_count: usize) {
+        let keep = (0..self.confusion.len())
+            .filter(|&idx| {
+                let support = self.confusion[idx].iter().sum::<usize>();
+                let predicted = self.confusion.iter().map(|row| row[idx]).sum::<usize>();
+                support >= min_count || predicted >= min_count
+            })
+            .collect::<Vec<_>>();
+        let confusion = keep
+            .iter()
+            .map(|&target_idx| keep.iter().map(|&pred_idx| self.confusion[target_idx][pred_idx]).collect())
+            .collect();
+        let mut numberer = Numberer::new();
+        for &idx in &keep {
+            numberer.number(self.numberer.idx2val[idx].clone());
+        }
+        self.confusion = confusion;
+        self.numberer = numberer;
+    }
+
+    /// Renumber the values in ascending order, see `sort_values_by`.
+    pub fn sort_values(&mut self) where V: Ord {
+        self.sort_values_by(Ord::cmp)
+    }
+
+    /// Grow the matrix to the number of known values.
+    fn grow(&mut self) {
+        while self.numberer.len() > self.confusion.len() {
+            self.confusion.push(vec![0; self.confusion.len()]);
+            self.confusion
+                .iter_mut()
+                .for_each(|row| row.push(0));
+        }
+    }
+}
+impl<V> Confusion<V> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn numberer(&self) -> &Numberer<V> {
+        &self.numberer
+    }
+
+    /// Precision of the value with index `idx`.
+    ///
+    /// Returns 0 if the value was never predicted.
+    pub fn precision(&self, idx: usize) -> f32 {
+        let predicted = self.confusion.iter().map(|row| row[idx]).sum::<usize>();
+        ratio(self.confusion[idx][idx], predicted)
+    }
+
+    /// Recall of the value with index `idx`.
+    ///
+    /// Returns 0 if the value never occurs as target.
+    pub fn recall(&self, idx: usize) -> f32 {
+        let support = self.confusion[idx].iter().sum::<usize>();
+        ratio(self.confusion[idx][idx], support)
+    }
+
+    /// Cohen's kappa of targets and predictions.
+    ///
+    /// Computed as `(po - pe) / (1 - pe)`, where `po` is the observed
+    /// agreement and `pe` the agreement expected from the target and
+    /// prediction marginals. If `pe` is 1, i.e. only a single value
+    /// occurs, agreement is perfect and 1 is returned. Returns 0 for an
+    /// empty matrix.
+    pub fn kappa(&self) -> f64 {
+        let total = self.confusion.iter().flatten().sum::<usize>() as f64;
+        if total == 0. {
+            return 0.;
+        }
+        let observed = (0..self.confusion.len()).map(|idx| self.confusion[idx][idx]).sum::<usize>() as f64 / total;
+        let expected = (0..self.confusion.len())
+            .map(|idx| {
+                let target = self.confusion[idx].iter().sum::<usize>() as f64;
+                let predicted = self.confusion.iter().map(|row| row[idx]).sum::<usize>() as f64;
+                target * predicted
+            })
+            .sum::<f64>()
+            / (total * total);
+        if expected == 1. {
+            1.
+        } else {
+            (observed - expected) / (1. - expected)
+        }
+    }
+
+    /// Multiclass Matthews correlation coefficient of targets and
+    /// predictions.
+    ///
+    /// Uses Gorodkin's generalization
+    /// `(c * s - sum_k p_k * t_k) / sqrt((s^2 - sum_k p_k^2) * (s^2 - sum_k t_k^2))`,
+    /// where `c` is the number of correct predictions, `s` the total
+    /// count, and `t_k` and `p_k` the number of times value `k` is the
+    /// target and the prediction. Returns 0 if the denominator is 0, i.e.
+    /// if all targets or all predictions have the same value, and for an
+    /// empty matrix.
+    pub fn mcc(&self) -> f64 {
+        let n = self.confusion.len();
+        let total = self.confusion.iter().flatten().sum::<usize>() as f64;
+        let correct = (0..n).map(|idx| self.confusion[idx][idx]).sum::<usize>() as f64;
+        let targets = (0..n)
+            .map(|idx| self.confusion[idx].iter().sum::<usize>() as f64)
+            .collect::<Vec<_>>();
+        let predicted = (0..n)
+            .map(|idx| self.confusion.iter().map(|row| row[idx]).sum::<usize>() as f64)
+            .collect::<Vec<_>>();
+        let covariance = correct * total - targets.iter().zip(&predicted).map(|(t, p)| t * p).sum::<f64>();
+        let target_variance = total * total - targets.iter().map(|t| t * t).sum::<f64>();
+        let predicted_variance = total * total - predicted.iter().map(|p| p * p).sum::<f64>();
+        let denominator = (target_variance * predicted_variance).sqrt();
+        if denominator == 0. {
+            0.
+        } else {
+            covariance / denominator
+        }
+    }
+
+    /// Get the non-zero cells as (target, prediction, count).
+    ///
+    /// Cells are ordered by the matrix position.
+    pub fn entries(&self) -> impl Iterator<Item = (&V, &V, usize)> {
+        self.confusion.iter().enumerate().flat_map(move |(target_idx, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|&(_, &count)| count != 0)
+                .map(move |(pred_idx, &count)| {
+                    (&self.numberer.idx2val[target_idx], &self.numberer.idx2val[pred_idx], count)
+                })
+        })
+    }
+
+    /// Get the values that were predicted but never occur as target.
+    ///
+    /// Returns every value with an empty row together with its column
+    /// sum, i.e. the number of times it was predicted.
+    pub fn prediction_only(&self) -> Vec<(&V, usize)> {
+        self.numberer
+            .idx2val
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| self.confusion[idx].iter().sum::<usize>() == 0)
+            .map(|(idx, val)| (val, self.confusion.iter().map(|row| row[idx]).sum()))
+            .collect()
+    }
+}
+
+impl<V> Confusion<V> where V: Clone + Hash + Eq + ToString {
+    /// Get the `k` most frequent confusions.
+    ///
+    /// Returns the off-diagonal cells with the highest counts as
+    /// (target, prediction, count), ordered by descending count. Ties
+    /// are ordered by the matrix position.
+    pub fn top_confusions(&self, k: usize) -> Vec<(String, String, usize)> {
+        let mut cells = Vec::new();
+        for (target_idx, row) in self.confusion.iter().enumerate() {
+            for (pred_idx, &count) in row.iter().enumerate() {
+                if target_idx != pred_idx && count != 0 {
+                    cells.push((target_idx, pred_idx, count));
+                }
+            }
+        }
+        cells.sort_by_key(|&(_, _, count)| Reverse(count));
+        cells
+            .into_iter()
+            .take(k)
+            .map(|(target_idx, pred_idx, count)| {
+                (
+                    self.numberer.idx2val[target_idx].to_string(),
+                    self.numberer.idx2val[pred_idx].to_string(),
+                    count,
+                )
+            })
+            .collect()
+    }
+
+    pub fn write_accuracies(&self, mut w: impl Write) -> Result<(), Error> {
+        let excluded = self.aggregate_excluded_idx();
+        for (idx, item) in self.numberer.idx2val.iter().map(V::to_string).enumerate() {
+            if Some(idx) == excluded {
+                continue;
+            }
+            let row = &self.confusion[idx];
+            let correct = row[idx];
+            let total = row.iter().sum::<usize>();
+            let acc = ratio(correct, total);
+            writeln!(w, "{}\t{}\t{}", item, total, self.rounding.format(acc, self.precision))?;
+        }
+        Ok(())
+    }
+
+    /// Write the confusion matrix with every column normalized to sum to 1.
+    ///
+    /// Cell `(gold, pred)` holds the fraction of tokens predicted as `pred`
+    /// that have the gold value `gold`. Columns without any prediction are
+    /// written as zeros.
+    pub fn write_normalized_col(&self, mut w: impl Write) -> Result<(), Error> {
+        writeln!(w, "{}\t{}", self.name, self.numberer.idx2val.iter().map(ToString::to_string).join("\t"))?;
+        let col_totals = (0..self.confusion.len())
+            .map(|j| self.confusion.iter().map(|row| row[j]).sum::<usize>())
+            .collect::<Vec<_>>();
+        for (idx, val) in self.numberer.idx2val.iter().enumerate() {
+            let normalized = self.confusion[idx]
+                .iter()
+                .zip(&col_totals)
+                .map(|(&n, &total)| ratio(n, total))
+                .map(|frac| self.rounding.format(frac, self.precision))
+                .join("\t");
+            writeln!(w, "{}\t{}", val.to_string(), normalized)?;
+        }
+        Ok(())
+    }
+
+    /// Write support, precision, recall and F1 for every value.
+    ///
+    /// See `write_prf_beta`.
+    pub fn write_prf(&self, w: impl Write) -> Result<(), Error> {
+        self.write_prf_beta(w, 1.)
+    }
+
+    /// Write support, precision, recall and F-beta for every value.
+    ///
+    /// Values are sorted by their support in descending order.
+    ///
+    /// F-beta is computed as `(1 + beta^2) * P * R / (beta^2 * P + R)`,
+    /// `beta = 1` gives the harmonic mean of precision and recall (F1).
+    /// Values with a larger beta weigh recall higher, smaller values
+    /// weigh precision higher. The table ends with macro averages over
+    /// the values that occur as target, as in `macro_precision`, and the
+    /// micro averages. Since every token carries exactly one target and
+    /// one predicted value, micro precision, recall and F-beta all equal
+    /// the accuracy. The value excluded from the aggregates is not
+    /// written, see `exclude_from_aggregates`.
+    pub fn write_prf_beta(&self, mut w: impl Write, beta: f32) -> Result<(), Error> {
+        let excluded = self.aggregate_excluded_idx();
+        let supported = self.supported_indices();
+        let mut macro_p = 0.;
+        let mut macro_r = 0.;
+        let mut macro_f = 0.;
+        let mut total_correct = 0;
+        let mut full_total = 0;
+        let supports = self.confusion.iter().map(|row| row.iter().sum::<usize>()).collect::<Vec<_>>();
+        let mut order = (0..self.confusion.len())
+            .filter(|&idx| Some(idx) != excluded)
+            .collect::<Vec<_>>();
+        order.sort_by(|&a, &b| supports[b].cmp(&supports[a]));
+        for idx in order {
+            let item = self.numberer.idx2val[idx].to_string();
+            let support = supports[idx];
+            let p = self.aggregate_precision(idx);
+            let r = self.recall(idx);
+            let f = f_beta(p, r, beta);
+            if supported.contains(&idx) {
+                macro_p += p;
+                macro_r += r;
+                macro_f += f;
+            }
+            total_correct += self.confusion[idx][idx];
+            full_total += support;
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}",
+                item,
+                support,
+                self.rounding.format(p, self.precision),
+                self.rounding.format(r, self.precision),
+                self.rounding.format(f, self.precision)
+            )?;
+        }
+        let n_supported = supported.len().max(1) as f32;
+        writeln!(
+            w,
+            "macro\t{}\t{}\t{}\t{}",
+            full_total,
+            self.rounding.format(macro_p / n_supported, self.precision),
+            self.rounding.format(macro_r / n_supported, self.precision),
+            self.rounding.format(macro_f / n_supported, self.precision)
+        )?;
+        let acc = self.rounding.format(ratio(total_correct, full_total), self.precision);
+        writeln!(w, "micro\t{}\t{}\t{}\t{}", full_total, acc, acc, acc)?;
+        Ok(())
+    }
+
+    /// Write the confusion matrix.
+    ///
+    /// Rows are gold values, columns predicted values. Each row ends with
+    /// the number of gold tokens and the recall of its value. The matrix
+    /// is followed by a `total` row with the number of predictions of
+    /// each value, a `precision` row with the precision of each value,
+    /// the overall accuracy and the macro precision and recall, see
+    /// `macro_precision`. Cells are separated by `sep`. The corner cell
+    /// holds the name of the matrix, or the axis label `gold\predicted`
+    /// if `header` is set. See `exclude_from_aggregates` for leaving a
+    /// value out of the recalls, precisions and the accuracy.
+    pub fn write(&self, mut w: impl Write, sep: &str, header: bool) -> Result<(), Error> {
+        let excluded = self.aggregate_excluded_idx();
+        let corner = if header { "gold\\predicted" } else { self.name.as_str() };
+        writeln!(
+            w,
+            "{}{}{}{}total{}recall",
+            corner,
+            sep,
+            self.numberer.idx2val.iter().map(ToString::to_string).join(sep),
+            sep,
+            sep
+        )?;
+        let mut total_correct = 0;
+        let mut full_total = 0;
+        for (idx, val) in self.numberer.idx2val.iter().enumerate() {
+            let row = &self.confusion[idx];
+            let cells = row.iter().map(|n| n.to_string()).join(sep);
+            let total = row.iter().sum::<usize>();
+            if Some(idx) == excluded {
+                writeln!(w, "{}{}{}{}{}{}", val.to_string(), sep, cells, sep, total, sep)?;
+                continue;
+            }
+            let correct = row[idx];
+            total_correct += correct;
+            full_total += total;
+            let acc = ratio(correct, total);
+            writeln!(
+                w,
+                "{}{}{}{}{}{}{}",
+                val.to_string(),
+                sep,
+                cells,
+                sep,
+                total,
+                sep,
+                self.rounding.format(acc, self.precision)
+            )?;
+        }
+        let mut delim = String::new();
+        let mut totals = String::from("total");
+        let mut precs = String::from("precision");
+        for i in 0..self.confusion.len() {
+            delim.push_str(&format!("{}____", sep));
+            totals.push_str(&format!("{}{}", sep, self.confusion.iter().map(|row| row[i]).sum::<usize>()));
+            precs.push_str(sep);
+            if Some(i) == excluded {
+                continue;
+            }
+            precs.push_str(&self.rounding.format(self.aggregate_precision(i), self.precision));
+        }
+        totals.push_str(&format!("{}{}", sep, self.confusion.iter().flatten().sum::<usize>()));
+        writeln!(w, "{}", delim)?;
+        writeln!(w, "{}", totals)?;
+        writeln!(w, "{}", precs)?;
+        let acc = ratio(total_correct, full_total);
+        writeln!(w, "acc: {}", self.rounding.format(acc, self.precision))?;
+        writeln!(w, "macro P: {}", self.rounding.format(self.macro_precision(), self.precision))?;
+        writeln!(w, "macro R: {}", self.rounding.format(self.macro_recall(), self.precision))?;
+        Ok(())
+    }
+}
+
+/// Formats the matrix as by `write`, with labeled axes.
+impl<V> Display for Confusion<V> where V: Clone + Hash + Eq + ToString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = Vec::new();
+        self.write(&mut buf, "\t", false).map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&buf))
+    }
+}
+
+pub struct Numberer<V>{
+    val2idx: HashMap<V, usize>,
+    idx2val: Vec<V>,
+}
+
+impl<V> Default for Numberer<V> where V: Clone + Hash + Eq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Numberer<V> where V: Clone + Hash + Eq {
+    pub fn new() -> Self {
+        Numberer {
+            val2idx: HashMap::new(),
+            idx2val: Vec::new(),
+        }
+    }
+
+    fn number<S>(&mut self, val: S) -> usize where S: Into<V> {
+        let val = val.into();
+        if let Some(idx) = self.val2idx.get(&val) {
+            *idx
+        } else {
+            let n_vals = self.val2idx.len();
+            self.val2idx.insert(val.clone(), n_vals);
+            self.idx2val.push(val);
+            n_vals
+        }
+    }
+
+    pub fn get_number(&self, val: &V) -> Option<usize> {
+        self.val2idx.get(val).copied()
+    }
+}
+
+impl<V> Numberer<V> {
+    pub fn len(&self) -> usize {
+        self.idx2val.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idx2val.is_empty()
+    }
+
+    pub fn get_val(&self, idx: usize) -> Option<&V> {
+        self.idx2val.get(idx)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::hash::Hash;
+
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Confusion, Numberer};
+    use crate::metrics::{Rounding, DEFAULT_PRECISION};
+
+    /// Numberers are serialized as the sequence of their values, ordered
+    /// by number.
+    impl<V> Serialize for Numberer<V> where V: Serialize {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+            self.idx2val.serialize(serializer)
+        }
+    }
+
+    impl<'de, V> Deserialize<'de> for Numberer<V> where V: Deserialize<'de> + Clone + Hash + Eq {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+            let mut numberer = Numberer::new();
+            for val in Vec::<V>::deserialize(deserializer)? {
+                if numberer.val2idx.contains_key(&val) {
+                    return Err(D::Error::custom("numberer contains a value more than once"));
+                }
+                numberer.number(val);
+            }
+            Ok(numberer)
+        }
+    }
+
+    #[derive(Serialize)]
+    struct ConfusionRef<'a, V> {
+        name: &'a str,
+        numberer: &'a Numberer<V>,
+        confusion: &'a [Vec<usize>],
+    }
+
+    #[derive(Deserialize)]
+    #[serde(bound(deserialize = "V: Deserialize<'de> + Clone + Hash + Eq"))]
+    struct ConfusionData<V> {
+        name: String,
+        numberer: Numberer<V>,
+        confusion: Vec<Vec<usize>>,
+    }
+
+    /// The rounding mode, precision and the value excluded from the
+    /// aggregates are not serialized, deserialized matrices use the
+    /// defaults.
+    impl<V> Serialize for Confusion<V> where V: Serialize {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+            ConfusionRef {
+                name: &self.name,
+                numberer: &self.numberer,
+                confusion: &self.confusion,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, V> Deserialize<'de> for Confusion<V> where V: Deserialize<'de> + Clone + Hash + Eq {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+            let data = ConfusionData::<V>::deserialize(deserializer)?;
+            let n_vals = data.numberer.len();
+            if data.confusion.len() != n_vals || data.confusion.iter().any(|row| row.len() != n_vals) {
+                return Err(D::Error::custom(format!(
+                    "confusion matrix must have {} rows and columns, one per numberer value",
+                    n_vals
+                )));
+            }
+            Ok(Confusion {
+                confusion: data.confusion,
+                numberer: data.numberer,
+                name: data.name,
+                rounding: Rounding::default(),
+                precision: DEFAULT_PRECISION,
+                aggregate_excluded: None,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Confusion;
+
+    /// Targets a, a, a, b predicted as a, a, b, b.
+    fn example() -> Confusion<String> {
+        let mut confusion = Confusion::new("test");
+        confusion.insert("a", "a");
+        confusion.insert("a", "a");
+        confusion.insert("a", "b");
+        confusion.insert("b", "b");
+        confusion
+    }
+
+    fn entries(confusion: &Confusion<String>) -> Vec<(String, String, usize)> {
+        let numberer = confusion.numberer();
+        let mut entries = Vec::new();
+        for (target_idx, row) in confusion.confusion.iter().enumerate() {
+            for (pred_idx, &count) in row.iter().enumerate() {
+                if count != 0 {
+                    let target = numberer.get_val(target_idx).unwrap().clone();
+                    let pred = numberer.get_val(pred_idx).unwrap().clone();
+                    entries.push((target, pred, count));
+                }
+            }
+        }
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn kappa() {
+        // po = 3/4, pe = (3 * 2 + 1 * 2) / 16 = 1/2.
+        assert!((example().kappa() - 0.5).abs() < 1e-9);
+
+        let mut single = Confusion::<String>::new("single");
+        single.insert("a", "a");
+        assert_eq!(single.kappa(), 1.);
+        assert_eq!(Confusion::<String>::new("empty").kappa(), 0.);
+    }
+
+    #[test]
+    fn mcc() {
+        // (3 * 4 - 8) / sqrt((16 - 8) * (16 - 10)).
+        assert!((example().mcc() - 4. / 48f64.sqrt()).abs() < 1e-9);
+
+        let mut constant = Confusion::<String>::new("constant");
+        constant.insert("a", "a");
+        constant.insert("b", "a");
+        assert_eq!(constant.mcc(), 0.);
+        assert_eq!(Confusion::<String>::new("empty").mcc(), 0.);
+    }
+
+    #[test]
+    fn macro_averages_ignore_prediction_only_values() {
+        let mut confusion = example();
+        confusion.insert("b", "c");
+        // Recall of a is 2/3, of b 1/2, c is never a target.
+        assert!((confusion.macro_recall() - (2. / 3. + 0.5) / 2.).abs() < 1e-6);
+        // Precision of a is 1, of b 1/2.
+        assert!((confusion.macro_precision() - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn display_uses_the_default_layout() {
+        let display = example().to_string();
+        assert!(display.starts_with("test\ta\tb\ttotal\trecall\n"));
+
+        let mut header = Vec::new();
+        example().write(&mut header, ",", true).unwrap();
+        assert!(String::from_utf8(header).unwrap().starts_with("gold\\predicted,a,b,total,recall\n"));
+    }
+
+    #[test]
+    fn merge_remaps_values() {
+        let mut first = Confusion::<String>::new("test");
+        first.insert("x", "y");
+        let mut second = Confusion::<String>::new("test");
+        second.insert("y", "y");
+        second.insert("z", "x");
+        second.insert("x", "y");
+        first.merge(second);
+
+        let mut all = Confusion::<String>::new("test");
+        all.insert("x", "y");
+        all.insert("y", "y");
+        all.insert("z", "x");
+        all.insert("x", "y");
+        assert_eq!(entries(&first), entries(&all));
+        assert_eq!(
+            entries(&first),
+            vec![
+                ("x".to_owned(), "y".to_owned(), 2),
+                ("y".to_owned(), "y".to_owned(), 1),
+                ("z".to_owned(), "x".to_owned(), 1)
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let confusion = example();
+        let json = serde_json::to_string(&confusion).unwrap();
+        let read = serde_json::from_str::<Confusion<String>>(&json).unwrap();
+        assert_eq!(read.name, "test");
+        assert_eq!(entries(&read), entries(&confusion));
+        assert_eq!(read.numberer().get_val(1).map(String::as_str), Some("b"));
+
+        let invalid = r#"{"name":"test","numberer":["a","b"],"confusion":[[1,0]]}"#;
+        assert!(serde_json::from_str::<Confusion<String>>(invalid).is_err());
+    }
+}
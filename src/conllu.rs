@@ -0,0 +1,252 @@
+//! CoNLL-U reader.
+//!
+//! `conllx::io::Reader` only understands the 10 CoNLL-X columns. This
+//! reader accepts CoNLL-U input and converts every sentence into a
+//! `conllx` `Sentence`, so it can be scored like CoNLL-X input:
+//!
+//! * comment lines (`# ...`) are skipped, except that `# key = value`
+//!   comments can be read with `Reader::read_sentence_with_metadata`;
+//! * multiword token ranges (`1-2`) and empty nodes (`5.1`) are skipped,
+//!   the remaining tokens must be numbered `1..=n` (see
+//!   `Reader::check_ids`) and keep their 1-based positions;
+//! * UPOS is stored as CPOS, XPOS as POS;
+//! * the DEPS and MISC columns are ignored unless enhanced dependencies
+//!   are collapsed (see `Reader::collapse_enhanced`).
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use conllx::graph::{DepTriple, Sentence};
+use conllx::io::ReadSentence;
+use conllx::token::{Features, Token};
+use failure::{format_err, Error};
+
+use crate::RootHead;
+
+/// Sentence metadata from `# key = value` comments.
+pub type Metadata = BTreeMap<String, String>;
+
+/// A reader for CoNLL-U sentences.
+pub struct Reader<R> {
+    read: R,
+    collapse_enhanced: bool,
+    check_ids: bool,
+    root_head: RootHead,
+    sentence_idx: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(read: R) -> Self {
+        Reader {
+            read,
+            collapse_enhanced: false,
+            check_ids: true,
+            root_head: RootHead::default(),
+            sentence_idx: 0,
+        }
+    }
+
+    /// Check that the token ids of every sentence are `1..=n`.
+    ///
+    /// Tokens are identified by their position, so gaps, duplicates or
+    /// out-of-order ids would silently attach tokens to the wrong heads.
+    /// Enabled by default, a sentence with unexpected ids is an error.
+    pub fn check_ids(mut self, check: bool) -> Self {
+        self.check_ids = check;
+        self
+    }
+
+    /// Set the HEAD value of tokens attached to the root, `0` by default.
+    ///
+    /// Applies to the HEAD column and to the heads of collapsed DEPS arcs.
+    pub fn root_head(mut self, root_head: RootHead) -> Self {
+        self.root_head = root_head;
+        self
+    }
+
+    /// Derive the basic dependency of a token from its enhanced graph.
+    ///
+    /// When enabled, the HEAD and DEPREL columns are ignored. Instead, the
+    /// first arc listed in DEPS whose head is a regular token (or the root)
+    /// becomes the basic head and relation. Arcs headed by empty nodes are
+    /// skipped. Tokens without such an arc are left without a head.
+    pub fn collapse_enhanced(mut self, collapse: bool) -> Self {
+        self.collapse_enhanced = collapse;
+        self
+    }
+}
+
+impl<R: BufRead> ReadSentence for Reader<R> {
+    fn read_sentence(&mut self) -> Result<Option<Sentence>, Error> {
+        Ok(self.read_sentence_with_id()?.map(|(_, sentence)| sentence))
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Read a sentence together with the value of its `# sent_id = ...`
+    /// comment, if any.
+    pub fn read_sentence_with_id(&mut self) -> Result<Option<(Option<String>, Sentence)>, Error> {
+        Ok(self
+            .read_sentence_with_metadata()?
+            .map(|(mut metadata, sentence)| (metadata.remove("sent_id"), sentence)))
+    }
+
+    /// Read a sentence together with its `# key = value` comments.
+    ///
+    /// If a key occurs more than once, the last value is used.
+    pub fn read_sentence_with_metadata(&mut self) -> Result<Option<(Metadata, Sentence)>, Error> {
+        let mut line = String::new();
+        let mut sentence = Sentence::new();
+        let mut edges = Vec::new();
+        let mut metadata = Metadata::new();
+
+        loop {
+            line.clear();
+            let eof = self.read.read_line(&mut line)? == 0;
+
+            if eof || line.trim().is_empty() {
+                if sentence.len() == 1 {
+                    if eof {
+                        return Ok(None);
+                    }
+                    metadata.clear();
+                    continue;
+                }
+
+                for edge in edges {
+                    check_head(&sentence, &edge)?;
+                    sentence.dep_graph_mut().add_deprel::<String>(edge);
+                }
+                self.sentence_idx += 1;
+                return Ok(Some((metadata, sentence)));
+            }
+
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some((key, value)) = parse_metadata(comment) {
+                    metadata.insert(key.to_owned(), value.to_owned());
+                }
+                continue;
+            }
+
+            let fields = line.trim_end_matches(&['\n', '\r'][..]).split('\t').collect::<Vec<_>>();
+            if fields.len() != 10 {
+                return Err(format_err!(
+                    "Expected 10 columns in CoNLL-U line, found {}: {}",
+                    fields.len(),
+                    line.trim()
+                ));
+            }
+
+            // Multiword token ranges and empty nodes are not part of the
+            // basic dependency tree.
+            if fields[0].contains('-') || fields[0].contains('.') {
+                continue;
+            }
+
+            if self.check_ids && fields[0] != sentence.len().to_string() {
+                return Err(format_err!(
+                    "Sentence {}: expected token id {}, found {}",
+                    self.sentence_idx,
+                    sentence.len(),
+                    fields[0]
+                ));
+            }
+
+            let mut token = Token::new(fields[1]);
+            token.set_lemma(string_field(fields[2]));
+            token.set_cpos(string_field(fields[3]));
+            token.set_pos(string_field(fields[4]));
+            token.set_features(string_field(fields[5]).map(Features::from_string));
+            let dependent = sentence.push(token);
+
+            let head_rel = if self.collapse_enhanced {
+                primary_enhanced_arc(fields[8], dependent, self.root_head)?
+            } else {
+                match string_field(fields[6]) {
+                    Some(head) => Some((parse_head(head, dependent, self.root_head)?, string_field(fields[7]))),
+                    None => None,
+                }
+            };
+
+            if let Some((head, rel)) = head_rel {
+                edges.push(DepTriple::new(head, rel.map(ToOwned::to_owned), dependent));
+            }
+        }
+    }
+}
+
+/// Read the `sent_id` of every sentence.
+///
+/// Returns `None` if a sentence has no `sent_id`.
+pub fn read_sent_ids<R: BufRead>(reader: &mut Reader<R>) -> Result<Option<Vec<String>>, Error> {
+    let mut ids = Vec::new();
+    let mut complete = true;
+    while let Some((id, _)) = reader.read_sentence_with_id()? {
+        match id {
+            Some(id) => ids.push(id),
+            None => complete = false,
+        }
+    }
+    Ok(if complete { Some(ids) } else { None })
+}
+
+/// Get the key and value of a `key = value` comment.
+fn parse_metadata(comment: &str) -> Option<(&str, &str)> {
+    let mut parts = comment.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some((key, value))
+}
+
+fn check_head(sentence: &Sentence, edge: &DepTriple<String>) -> Result<(), Error> {
+    if edge.head() >= sentence.len() {
+        return Err(format_err!(
+            "Head {} of token {} is out of bounds",
+            edge.head(),
+            edge.dependent()
+        ));
+    }
+    Ok(())
+}
+
+/// Get the first DEPS arc that is not headed by an empty node.
+fn primary_enhanced_arc(
+    deps: &str,
+    dependent: usize,
+    root_head: RootHead,
+) -> Result<Option<(usize, Option<&str>)>, Error> {
+    if deps == "_" {
+        return Ok(None);
+    }
+
+    for arc in deps.split('|') {
+        let mut parts = arc.splitn(2, ':');
+        let head = parts.next().unwrap_or("");
+        if head.contains('.') {
+            continue;
+        }
+        let rel = parts.next();
+        return Ok(Some((parse_head(head, dependent, root_head)?, rel)));
+    }
+
+    Ok(None)
+}
+
+fn parse_head(head: &str, dependent: usize, root_head: RootHead) -> Result<usize, Error> {
+    if root_head.is_root(head, dependent) {
+        return Ok(0);
+    }
+    head.parse()
+        .map_err(|_| format_err!("Cannot parse head: {}", head))
+}
+
+fn string_field(field: &str) -> Option<&str> {
+    if field == "_" {
+        None
+    } else {
+        Some(field)
+    }
+}
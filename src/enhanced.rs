@@ -0,0 +1,300 @@
+//! Enhanced dependency evaluation.
+//!
+//! Enhanced dependencies are stored in the DEPS column of CoNLL-U files as
+//! `head:relation` pairs separated by `|`. In contrast to basic
+//! dependencies, a node can have several heads, and empty nodes (`5.1`)
+//! are part of the graph. Enhanced graphs are therefore scored as sets of
+//! `(head, relation, dependent)` arcs, giving enhanced labeled attachment
+//! (ELAS) precision, recall and F1. Unlabeled scores (EUAS) compare the
+//! sets of `(head, dependent)` pairs.
+
+use std::collections::BTreeSet;
+use std::io::BufRead;
+use std::ops::AddAssign;
+
+use failure::{format_err, Error};
+
+use crate::metrics::{f_beta, ratio};
+
+/// An enhanced dependency arc.
+///
+/// Heads and dependents are CoNLL-U node identifiers, such as `3` or
+/// `5.1`. The artificial root is `0`.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct EnhancedArc {
+    pub head: String,
+    pub relation: String,
+    pub dependent: String,
+}
+
+/// The enhanced dependency graph of a sentence.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnhancedGraph {
+    /// Identifiers of the tokens and empty nodes in sentence order.
+    pub nodes: Vec<String>,
+
+    /// Arcs of the graph.
+    pub arcs: BTreeSet<EnhancedArc>,
+}
+
+impl EnhancedGraph {
+    /// Get the `(head, dependent)` pairs of the arcs.
+    ///
+    /// A pair that is connected by arcs with different relations is
+    /// only included once.
+    pub fn unlabeled_arcs(&self) -> BTreeSet<(&str, &str)> {
+        self.arcs
+            .iter()
+            .map(|arc| (arc.head.as_str(), arc.dependent.as_str()))
+            .collect()
+    }
+}
+
+/// A reader for the enhanced dependency graphs of CoNLL-U sentences.
+///
+/// Comments and multiword token ranges are skipped.
+pub struct EnhancedReader<R> {
+    read: R,
+}
+
+impl<R: BufRead> EnhancedReader<R> {
+    pub fn new(read: R) -> Self {
+        EnhancedReader { read }
+    }
+
+    /// Read the enhanced graph of the next sentence.
+    pub fn read_graph(&mut self) -> Result<Option<EnhancedGraph>, Error> {
+        let mut line = String::new();
+        let mut graph = EnhancedGraph::default();
+
+        loop {
+            line.clear();
+            let eof = self.read.read_line(&mut line)? == 0;
+
+            if eof || line.trim().is_empty() {
+                if graph.nodes.is_empty() {
+                    if eof {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+
+                check_heads(&graph)?;
+                return Ok(Some(graph));
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.trim_end_matches(&['\n', '\r'][..]).split('\t').collect::<Vec<_>>();
+            if fields.len() != 10 {
+                return Err(format_err!(
+                    "Expected 10 columns in CoNLL-U line, found {}: {}",
+                    fields.len(),
+                    line.trim()
+                ));
+            }
+
+            if fields[0].contains('-') {
+                continue;
+            }
+
+            let dependent = fields[0];
+            graph.nodes.push(dependent.to_owned());
+            if fields[8] == "_" {
+                continue;
+            }
+
+            for arc in fields[8].split('|') {
+                let mut parts = arc.splitn(2, ':');
+                let head = parts.next().unwrap_or("");
+                let relation = parts
+                    .next()
+                    .ok_or_else(|| format_err!("Enhanced arc of node {} without relation: {}", dependent, arc))?;
+                graph.arcs.insert(EnhancedArc {
+                    head: head.to_owned(),
+                    relation: relation.to_owned(),
+                    dependent: dependent.to_owned(),
+                });
+            }
+        }
+    }
+}
+
+/// Check that every head is the root or a node of the sentence.
+fn check_heads(graph: &EnhancedGraph) -> Result<(), Error> {
+    for arc in &graph.arcs {
+        if arc.head != "0" && !graph.nodes.contains(&arc.head) {
+            return Err(format_err!(
+                "Enhanced head {} of node {} is not in the sentence",
+                arc.head,
+                arc.dependent
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Enhanced labeled attachment counts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ElasCounts {
+    /// Number of gold arcs.
+    pub gold: usize,
+
+    /// Number of predicted arcs.
+    pub predicted: usize,
+
+    /// Number of predicted arcs that are in the gold graph.
+    pub correct: usize,
+
+    /// Number of gold `(head, dependent)` pairs.
+    pub gold_unlabeled: usize,
+
+    /// Number of predicted `(head, dependent)` pairs.
+    pub predicted_unlabeled: usize,
+
+    /// Number of predicted `(head, dependent)` pairs that are in the
+    /// gold graph.
+    pub correct_unlabeled: usize,
+
+    /// Number of sentences.
+    pub sentences: usize,
+}
+
+impl ElasCounts {
+    /// Add the gold and predicted graphs of a sentence.
+    ///
+    /// Both graphs must have the same nodes. The UD guidelines do not allow
+    /// a node to be its own head, so a gold graph with a self-loop is an
+    /// error. A predicted self-loop is counted as an incorrect arc.
+    pub fn insert(&mut self, gold: &EnhancedGraph, pred: &EnhancedGraph) -> Result<(), Error> {
+        let sentence = self.sentences + 1;
+        if gold.nodes != pred.nodes {
+            return Err(format_err!(
+                "Sentence {}: validation and prediction have different nodes",
+                sentence
+            ));
+        }
+        if let Some(arc) = gold.arcs.iter().find(|arc| arc.head == arc.dependent) {
+            return Err(format_err!(
+                "Sentence {}: node {} is its own head in the validation data",
+                sentence,
+                arc.dependent
+            ));
+        }
+
+        self.sentences += 1;
+        self.gold += gold.arcs.len();
+        self.predicted += pred.arcs.len();
+        self.correct += gold.arcs.intersection(&pred.arcs).count();
+        let gold_unlabeled = gold.unlabeled_arcs();
+        let pred_unlabeled = pred.unlabeled_arcs();
+        self.gold_unlabeled += gold_unlabeled.len();
+        self.predicted_unlabeled += pred_unlabeled.len();
+        self.correct_unlabeled += gold_unlabeled.intersection(&pred_unlabeled).count();
+        Ok(())
+    }
+
+    pub fn unlabeled_precision(&self) -> f32 {
+        ratio(self.correct_unlabeled, self.predicted_unlabeled)
+    }
+
+    pub fn unlabeled_recall(&self) -> f32 {
+        ratio(self.correct_unlabeled, self.gold_unlabeled)
+    }
+
+    pub fn unlabeled_f1(&self) -> f32 {
+        f_beta(self.unlabeled_precision(), self.unlabeled_recall(), 1.)
+    }
+
+    pub fn precision(&self) -> f32 {
+        ratio(self.correct, self.predicted)
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(self.correct, self.gold)
+    }
+
+    pub fn f1(&self) -> f32 {
+        f_beta(self.precision(), self.recall(), 1.)
+    }
+}
+
+impl AddAssign for ElasCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.gold += other.gold;
+        self.predicted += other.predicted;
+        self.correct += other.correct;
+        self.gold_unlabeled += other.gold_unlabeled;
+        self.predicted_unlabeled += other.predicted_unlabeled;
+        self.correct_unlabeled += other.correct_unlabeled;
+        self.sentences += other.sentences;
+    }
+}
+
+/// Compute ELAS counts of two CoNLL-U files.
+pub fn evaluate_enhanced<R, S>(
+    val_reader: &mut EnhancedReader<R>,
+    pred_reader: &mut EnhancedReader<S>,
+) -> Result<ElasCounts, Error>
+where
+    R: BufRead,
+    S: BufRead,
+{
+    let mut counts = ElasCounts::default();
+    loop {
+        match (val_reader.read_graph()?, pred_reader.read_graph()?) {
+            (Some(val), Some(pred)) => counts.insert(&val, &pred)?,
+            (None, None) => return Ok(counts),
+            _ => {
+                return Err(format_err!(
+                    "Validation and prediction have a different number of sentences"
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_enhanced, EnhancedReader};
+
+    static GOLD: &str = "\
+1\tDogs\tdog\tNOUN\tNNS\t_\t2\tnsubj\t2:nsubj|3:nsubj\t_
+2\tbark\tbark\tVERB\tVBP\t_\t0\troot\t0:root\t_
+3\thowl\thowl\tVERB\tVBP\t_\t2\tconj\t2:conj\t_
+";
+
+    /// The shared subject is missing, the conjunct has a wrong relation.
+    static PRED: &str = "\
+1\tDogs\tdog\tNOUN\tNNS\t_\t2\tnsubj\t2:nsubj\t_
+2\tbark\tbark\tVERB\tVBP\t_\t0\troot\t0:root\t_
+3\thowl\thowl\tVERB\tVBP\t_\t2\tobj\t2:obj\t_
+";
+
+    #[test]
+    fn elas() {
+        let counts = evaluate_enhanced(
+            &mut EnhancedReader::new(GOLD.as_bytes()),
+            &mut EnhancedReader::new(PRED.as_bytes()),
+        )
+        .unwrap();
+        assert_eq!((counts.gold, counts.predicted, counts.correct), (4, 3, 2));
+        assert!((counts.precision() - 2. / 3.).abs() < 1e-6);
+        assert_eq!(counts.recall(), 0.5);
+        assert_eq!(counts.unlabeled_precision(), 1.);
+        assert_eq!(counts.unlabeled_recall(), 0.75);
+        assert_eq!(counts.sentences, 1);
+    }
+
+    #[test]
+    fn unknown_head_is_an_error() {
+        let pred = PRED.replace("2:obj", "7:obj");
+        let result = evaluate_enhanced(
+            &mut EnhancedReader::new(GOLD.as_bytes()),
+            &mut EnhancedReader::new(pred.as_bytes()),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,2076 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::thread;
+
+use conllx::graph::{DepTriple, Sentence};
+use conllx::io::ReadSentence;
+use conllx::token::{Features, Token};
+use failure::{format_err, Error};
+use itertools::Itertools;
+
+use crate::metrics::DEFAULT_PRECISION;
+use crate::rng::SplitMix64;
+use crate::{ratio, strip_subtype, ClauseRelations, FeatsCounts, AttachmentCounts, Buckets, Confusion, RelPrf, RelRules, RootPrf, RootSummary, Rounding, SkipCounts, Summary, TokenWeights};
+
+/// Evaluation options.
+///
+/// The default options score every token.
+#[derive(Clone, Default)]
+pub struct EvalOptions {
+    /// Skip tokens whose validation POS starts with `PUNCT`.
+    pub skip_punctuation: bool,
+
+    /// Exclude punctuation from the attachment scores.
+    ///
+    /// A token is punctuation if its validation relation is `punct` or
+    /// its validation (C)POS tag is in `punct_tags`.
+    pub no_punct: bool,
+
+    /// Also exclude punctuation from the confusion matrices.
+    pub no_punct_confusion: bool,
+
+    /// Additional punctuation tags for `no_punct`.
+    pub punct_tags: HashSet<String>,
+
+    /// Compare validation and prediction forms case-insensitively.
+    pub ignore_case_forms: bool,
+
+    /// Do not check that validation and prediction forms match.
+    ///
+    /// Sentences must still have the same length.
+    pub no_form_check: bool,
+
+    /// Skip sentences with a length or form mismatch and validation
+    /// tokens without head or relation, rather than failing. Skipped
+    /// sentences and tokens are counted in `EvalResult::skipped`.
+    pub skip_mismatched: bool,
+
+    /// Exclude tokens with these validation relations from the
+    /// attachment scores and the confusion matrices.
+    pub ignore_rels: HashSet<String>,
+
+    /// Relation rewriting rules or mapping applied to both sides before
+    /// scoring.
+    pub rel_rules: Option<RelRules>,
+
+    /// Derive predicted relations from the clause ids of the predicted
+    /// tokens rather than using the predicted relations.
+    ///
+    /// Applied before `rel_rules`.
+    pub clause_relations: Option<ClauseRelations>,
+
+    /// Strip relation subtypes on both sides before scoring.
+    ///
+    /// Applied after `rel_rules`.
+    pub coarse_rels: bool,
+
+    /// Do not require a matching relation for LAS if the gold head is
+    /// the root.
+    pub ignore_root_rel: bool,
+
+    /// Maximum number of errors logged per sentence.
+    pub max_errors_per_sentence: Option<usize>,
+
+    /// Rounding mode of the confusion matrices.
+    pub rounding: Rounding,
+
+    /// Tag column used for POS accuracy.
+    pub pos_column: PosColumn,
+
+    /// Number of decimal places of the confusion matrix outputs,
+    /// `DEFAULT_PRECISION` if not set.
+    pub precision: Option<usize>,
+
+    /// Number of threads used for scoring.
+    ///
+    /// With more than one thread, all sentences are read before they
+    /// are scored.
+    pub threads: usize,
+
+    /// Only score the sentences with an index in `[start, end)`.
+    ///
+    /// The other sentences are still read, so that the sentences of
+    /// both inputs stay aligned.
+    pub sentence_range: Option<(usize, usize)>,
+
+    /// Report progress to standard error every this many sentences.
+    pub progress: Option<usize>,
+
+    /// Score only a random sample of the sentences, each sentence is
+    /// scored with this probability.
+    ///
+    /// Sentences that are not sampled are still read, so that the
+    /// sentences of both inputs stay aligned.
+    pub sample_rate: Option<f64>,
+
+    /// Seed of the random number generator used for sampling.
+    pub sample_seed: u64,
+
+    /// Number of sentence pairs at the start of the inputs that are read
+    /// but not scored.
+    pub skip: usize,
+
+    /// Stop after reading this many sentence pairs after the skipped
+    /// sentences. The remaining sentences are not read, so differences
+    /// in the number of sentences are not detected.
+    pub max_sentences: Option<usize>,
+
+    /// Token weights for the weighted attachment scores.
+    pub weights: Option<TokenWeights>,
+
+    /// Buckets for the distance confusion matrix.
+    ///
+    /// Exact distances are used if no buckets are given.
+    pub distance_buckets: Option<Buckets>,
+
+    /// Relations for which arc precision, recall and F1 are computed,
+    /// see `EvalResult::target_rel_prf`.
+    pub target_rels: Vec<String>,
+
+    /// Exclude the root from the accuracies and precisions of the
+    /// confusion matrices, see `Confusion::exclude_from_aggregates`.
+    pub confusion_exclude_root: bool,
+
+    /// Collect up to this many example tokens of every relation
+    /// confusion, see `EvalResult::confusion_examples`.
+    pub confusion_examples: Option<usize>,
+
+    /// Order the values of the confusion matrices canonically rather
+    /// than by first occurrence, see `EvalResult::sort_labels`.
+    pub sort_labels: bool,
+}
+
+/// Optional outputs that are written during evaluation.
+#[derive(Default)]
+pub struct EvalWriters {
+    /// Log of tokens with a wrong head or relation.
+    ///
+    /// Columns: sentence, token, form, gold head, gold relation,
+    /// predicted head, predicted relation.
+    pub errors: Option<Box<dyn Write>>,
+
+    /// Sentences with a wrong head or relation, see `write_error_sentence`.
+    pub error_sentences: Option<Box<dyn Write>>,
+
+    /// Gold data in CoNLL-U with the predictions in MISC, see
+    /// `write_merged`. Only sampled sentences are written, see
+    /// `EvalOptions::sample_rate`.
+    pub merged: Option<Box<dyn Write>>,
+}
+
+/// Error logs of a single sentence.
+#[derive(Default)]
+struct ErrorLogs<'a> {
+    tokens: Option<&'a mut dyn Write>,
+    sentences: Option<&'a mut dyn Write>,
+}
+
+/// Tag column used for POS accuracy.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PosColumn {
+    /// The coarse-grained tag, UPOS in CoNLL-U.
+    Upos,
+    /// The fine-grained tag, XPOS in CoNLL-U.
+    #[default]
+    Xpos,
+}
+
+impl PosColumn {
+    /// Get the tag of a token.
+    pub fn tag(self, token: &Token) -> Option<&str> {
+        match self {
+            PosColumn::Upos => token.cpos(),
+            PosColumn::Xpos => token.pos(),
+        }
+    }
+}
+
+impl FromStr for PosColumn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upos" => Ok(PosColumn::Upos),
+            "xpos" => Ok(PosColumn::Xpos),
+            _ => Err(format_err!("Unknown POS column: {}", s)),
+        }
+    }
+}
+
+/// Attachment direction of a token.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Direction {
+    /// The head precedes the token.
+    Left,
+    /// The head follows the token.
+    Right,
+    /// The token is attached to the artificial root.
+    Root,
+}
+
+impl Direction {
+    /// Get the direction of the attachment of `dependent` to `head`.
+    pub fn new(dependent: usize, head: usize) -> Self {
+        if head == 0 {
+            Direction::Root
+        } else if head < dependent {
+            Direction::Left
+        } else {
+            Direction::Right
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Left => f.write_str("left"),
+            Direction::Right => f.write_str("right"),
+            Direction::Root => f.write_str("root"),
+        }
+    }
+}
+
+/// Write attachment scores per bucket as TSV with a header row.
+///
+/// Columns: bucket, sentences, tokens, UAS and LAS.
+pub fn write_bucket_scores(
+    w: impl Write,
+    buckets: &[(String, usize, AttachmentCounts)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    write_counted_buckets(w, "sentences", buckets, rounding, precision)
+}
+
+/// Write attachment scores per relation frequency bucket as TSV with a
+/// header row.
+///
+/// Columns: bucket, relations, tokens, UAS and LAS.
+pub fn write_label_frequency_scores(
+    w: impl Write,
+    buckets: &[(String, usize, AttachmentCounts)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    write_counted_buckets(w, "relations", buckets, rounding, precision)
+}
+
+/// Write attachment scores per bucket, `unit` is the header of the
+/// column that counts the bucket members.
+fn write_counted_buckets(
+    mut w: impl Write,
+    unit: &str,
+    buckets: &[(String, usize, AttachmentCounts)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    writeln!(w, "bucket\t{}\ttokens\tuas\tlas", unit)?;
+    for (label, n_sentences, score) in buckets {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            label,
+            n_sentences,
+            score.total,
+            rounding.format(score.uas(), precision),
+            rounding.format(score.las(), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write attachment scores per label as TSV with a header row.
+///
+/// Columns: label, support, UAS and LAS. Rows are sorted by support
+/// in descending order.
+pub fn write_label_scores(
+    mut w: impl Write,
+    scores: &BTreeMap<String, AttachmentCounts>,
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let mut rows = scores.iter().collect::<Vec<_>>();
+    rows.sort_by_key(|(_, score)| Reverse(score.total));
+    writeln!(w, "label\tsupport\tuas\tlas")?;
+    for (label, score) in rows {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            label,
+            score.total,
+            rounding.format(score.uas(), precision),
+            rounding.format(score.las(), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write head attachment errors per label as TSV with a header row.
+///
+/// Columns: label, tokens with a wrong head, total tokens and error
+/// rate. Rows are sorted by the number of errors in descending order.
+pub fn write_attachment_errors(
+    mut w: impl Write,
+    scores: &BTreeMap<String, AttachmentCounts>,
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let mut rows = scores.iter().collect::<Vec<_>>();
+    rows.sort_by_key(|(_, score)| Reverse(score.total - score.correct_head));
+    writeln!(w, "label\terrors\ttotal\terror_rate")?;
+    for (label, score) in rows {
+        let errors = score.total - score.correct_head;
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            label,
+            errors,
+            score.total,
+            rounding.format(ratio(errors, score.total), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write head attachment errors per distance bucket and direction as
+/// TSV with a header row.
+///
+/// Columns: bucket, gold attachment direction, total tokens, tokens with
+/// a wrong head and error rate.
+pub fn write_distance_direction_errors(
+    mut w: impl Write,
+    scores: &[(String, Direction, AttachmentCounts)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    writeln!(w, "bucket\tdirection\ttotal\terrors\terror_rate")?;
+    for (label, direction, score) in scores {
+        let errors = score.total - score.correct_head;
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            label,
+            direction,
+            score.total,
+            errors,
+            rounding.format(ratio(errors, score.total), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a distance error histogram as TSV with a header row.
+///
+/// Columns: bucket, tokens, fraction of the tokens and a bar of `#`
+/// characters, one per percent of the tokens.
+pub fn write_distance_error_histogram(
+    mut w: impl Write,
+    histogram: &[(String, usize)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let total = histogram.iter().map(|(_, count)| count).sum();
+    writeln!(w, "error\ttokens\tfraction\tbar")?;
+    for (label, count) in histogram {
+        let fraction = ratio(*count, total);
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            label,
+            count,
+            rounding.format(fraction, precision),
+            "#".repeat((fraction * 100.).round() as usize)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the scores of corpus prefixes as TSV with a header row.
+///
+/// Columns: sentences, tokens, UAS and LAS of the prefix.
+pub fn write_cumulative_scores(
+    mut w: impl Write,
+    prefixes: &[(usize, AttachmentCounts)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    writeln!(w, "sentences\ttokens\tuas\tlas")?;
+    for (n_sentences, score) in prefixes {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}",
+            n_sentences,
+            score.total,
+            rounding.format(score.uas(), precision),
+            rounding.format(score.las(), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write sentence LAS strata as TSV with a header row.
+///
+/// Columns: stratum, sentences and fraction of the sentences. If
+/// `list_sentences` is set, a last column lists the sentences of every
+/// stratum, separated by commas. Sentences are identified as by
+/// `write_sentence_scores`.
+pub fn write_las_strata(
+    mut w: impl Write,
+    strata: &[(String, Vec<usize>)],
+    list_sentences: bool,
+    ids: Option<&[String]>,
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let n_sentences = strata.iter().map(|(_, indices)| indices.len()).sum();
+    write!(w, "las\tsentences\tfraction")?;
+    match (list_sentences, ids) {
+        (true, Some(_)) => writeln!(w, "\tsent_ids")?,
+        (true, None) => writeln!(w, "\tindices")?,
+        (false, _) => writeln!(w)?,
+    }
+    for (label, indices) in strata {
+        write!(
+            w,
+            "{}\t{}\t{}",
+            label,
+            indices.len(),
+            rounding.format(ratio(indices.len(), n_sentences), precision)
+        )?;
+        match (list_sentences, ids) {
+            (true, Some(ids)) => writeln!(w, "\t{}", indices.iter().map(|&idx| &ids[idx]).join(","))?,
+            (true, None) => writeln!(w, "\t{}", indices.iter().join(","))?,
+            (false, _) => writeln!(w)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write per-sentence scores as TSV with a header row.
+///
+/// Columns: sentence, tokens, correct heads, correct labeled heads, UAS
+/// and LAS. `indices` holds the 0-based input index of every sentence.
+/// If `ids` holds the `sent_id` of every input sentence, sentences are
+/// identified by their `sent_id` (column `sent_id`), otherwise by their
+/// index (column `index`).
+pub fn write_sentence_scores(
+    mut w: impl Write,
+    scores: &[AttachmentCounts],
+    indices: &[usize],
+    ids: Option<&[String]>,
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let id_column = if ids.is_some() { "sent_id" } else { "index" };
+    writeln!(w, "{}\ttokens\tcorrect_head\tcorrect_head_label\tuas\tlas", id_column)?;
+    for (&idx, score) in indices.iter().zip(scores) {
+        let id = match ids {
+            Some(ids) => ids[idx].clone(),
+            None => idx.to_string(),
+        };
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            id,
+            score.total,
+            score.correct_head,
+            score.correct_head_label,
+            rounding.format(score.uas(), precision),
+            rounding.format(score.las(), precision)
+        )?;
+    }
+    Ok(())
+}
+
+/// A token with a relation confusion.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfusionExample {
+    /// 0-based index of the sentence.
+    pub sentence: usize,
+
+    /// 1-based index of the token.
+    pub token: usize,
+
+    /// The forms of the sentence separated by spaces, with the token
+    /// marked as `[[form]]`.
+    pub text: String,
+}
+
+impl ConfusionExample {
+    fn new(sentence_idx: usize, token: usize, sentence: &Sentence) -> Self {
+        let text = sentence
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, node)| node.token().map(|t| (idx, t)))
+            .map(|(idx, t)| {
+                if idx == token {
+                    format!("[[{}]]", t.form())
+                } else {
+                    t.form().to_owned()
+                }
+            })
+            .join(" ");
+        ConfusionExample {
+            sentence: sentence_idx,
+            token,
+            text,
+        }
+    }
+}
+
+/// Write relation confusion examples as TSV with a header row.
+///
+/// Columns: sentence, token and the sentence text with the token
+/// marked, see `ConfusionExample`.
+pub fn write_confusion_examples(mut w: impl Write, examples: &[ConfusionExample]) -> Result<(), Error> {
+    writeln!(w, "sentence\ttoken\ttext")?;
+    for example in examples {
+        writeln!(w, "{}\t{}\t{}", example.sentence, example.token, example.text)?;
+    }
+    Ok(())
+}
+
+/// Evaluation result.
+pub struct EvalResult {
+    /// Number of tokens with a correct head.
+    pub correct_head: usize,
+
+    /// Number of tokens with a correct head and relation.
+    pub correct_head_label: usize,
+
+    /// Number of tokens with a correct relation, regardless of the head.
+    pub correct_label: usize,
+
+    /// Number of scored tokens with a gold lemma.
+    pub lemma_total: usize,
+
+    /// Number of scored tokens with a correct lemma.
+    pub correct_lemma: usize,
+
+    /// Number of scored tokens with a gold POS tag.
+    pub pos_total: usize,
+
+    /// Number of scored tokens with a correct POS tag.
+    pub correct_pos: usize,
+
+    /// Sum of the weights of the scored tokens, see
+    /// `EvalOptions::weights`.
+    pub weighted_total: f64,
+
+    /// Sum of the weights of the tokens with a correct head.
+    pub weighted_correct_head: f64,
+
+    /// Sum of the weights of the tokens with a correct head and relation.
+    pub weighted_correct_head_label: f64,
+
+    /// Morphological feature counts of the scored tokens.
+    pub feats: FeatsCounts,
+
+    /// Number of tokens that are connected to their gold head in either
+    /// direction.
+    pub correct_undirected: usize,
+
+    /// Number of scored tokens.
+    pub total: usize,
+
+    /// Number of sentences.
+    pub sentences: usize,
+
+    /// Attachment counts per sentence.
+    pub sentence_scores: Vec<AttachmentCounts>,
+
+    /// Number of tokens per sentence, including unscored tokens.
+    pub sentence_lengths: Vec<usize>,
+
+    /// Index of every scored sentence in the input. Differs from the
+    /// position in `sentence_scores` if sentences were sampled.
+    pub sentence_indices: Vec<usize>,
+
+    /// Attachment counts per gold relation.
+    pub rel_scores: BTreeMap<String, AttachmentCounts>,
+
+    /// Attachment scores per gold head distance and direction. Tokens
+    /// attached to the root have distance 0.
+    pub distance_direction_scores: BTreeMap<(usize, Direction), AttachmentCounts>,
+
+    /// Frequencies of absolute differences between the predicted and
+    /// gold head distance.
+    pub distance_errors: BTreeMap<usize, usize>,
+
+    /// Frequencies of signed differences between the predicted and gold
+    /// head distance, positive if the predicted head is too far away.
+    pub signed_distance_errors: BTreeMap<i64, usize>,
+
+    /// Attachment counts of content words, see `is_content_rel`.
+    pub content_scores: AttachmentCounts,
+
+    /// Attachment counts of tokens with a projective gold edge.
+    pub projective_scores: AttachmentCounts,
+
+    /// Attachment counts of tokens with a non-projective gold edge.
+    pub non_projective_scores: AttachmentCounts,
+
+    /// Attachment counts of sentences without non-projective gold edge.
+    pub projective_sentence_scores: AttachmentCounts,
+
+    /// Attachment counts of sentences with a non-projective gold edge.
+    pub non_projective_sentence_scores: AttachmentCounts,
+
+    /// Attachment counts per gold POS tag of `EvalOptions::pos_column`,
+    /// `_` for tokens without tag.
+    pub pos_scores: BTreeMap<String, AttachmentCounts>,
+
+    /// Root identification counts.
+    pub root_prf: RootPrf,
+
+    /// Arc counts of every relation in `EvalOptions::target_rels`.
+    pub target_rel_prf: BTreeMap<String, RelPrf>,
+
+    /// Number of root tokens whose relation was ignored.
+    pub ignored_root_rels: usize,
+
+    /// Number of scored tokens without a predicted head.
+    pub missing_heads: usize,
+
+    /// Number of sentences that were read but not scored, see
+    /// `EvalOptions::sample_rate`.
+    pub unsampled_sentences: usize,
+
+    /// Number of errors omitted from the error log.
+    pub suppressed_errors: usize,
+
+    /// Sentences and tokens that were skipped.
+    pub skipped: SkipCounts,
+
+    /// Example tokens per (gold, predicted) relation confusion, in
+    /// input order. Only collected if `EvalOptions::confusion_examples`
+    /// is set.
+    pub confusion_examples: BTreeMap<(String, String), Vec<ConfusionExample>>,
+
+    /// Relation confusion matrix.
+    pub deprel_confusion: Confusion<String>,
+
+    /// Head distance confusion matrix.
+    ///
+    /// Tokens attached to the root are labeled `ROOT` instead of their
+    /// distance to the root, which is not an attachment distance.
+    pub distance_confusion: Confusion<String>,
+
+    /// Attachment direction confusion matrix.
+    pub direction_confusion: Confusion<Direction>,
+}
+
+impl EvalResult {
+    fn empty(options: &EvalOptions) -> Self {
+        let mut deprel_confusion = Confusion::<String>::new("Deprels");
+        let mut distance_confusion = Confusion::<String>::new("Dists");
+        let mut direction_confusion = Confusion::<Direction>::new("Dirs");
+        deprel_confusion.set_rounding(options.rounding);
+        distance_confusion.set_rounding(options.rounding);
+        direction_confusion.set_rounding(options.rounding);
+        let precision = options.precision.unwrap_or(DEFAULT_PRECISION);
+        deprel_confusion.set_precision(precision);
+        distance_confusion.set_precision(precision);
+        direction_confusion.set_precision(precision);
+        if options.confusion_exclude_root {
+            deprel_confusion.exclude_from_aggregates(ROOT_REL.to_owned());
+            distance_confusion.exclude_from_aggregates(ROOT_DISTANCE.to_owned());
+            direction_confusion.exclude_from_aggregates(Direction::Root);
+        }
+
+        EvalResult {
+            correct_head: 0,
+            correct_head_label: 0,
+            correct_label: 0,
+            lemma_total: 0,
+            correct_lemma: 0,
+            pos_total: 0,
+            correct_pos: 0,
+            weighted_total: 0.,
+            weighted_correct_head: 0.,
+            weighted_correct_head_label: 0.,
+            feats: FeatsCounts::default(),
+            correct_undirected: 0,
+            total: 0,
+            sentences: 0,
+            sentence_scores: Vec::new(),
+            sentence_lengths: Vec::new(),
+            sentence_indices: Vec::new(),
+            rel_scores: BTreeMap::new(),
+            distance_direction_scores: BTreeMap::new(),
+            distance_errors: BTreeMap::new(),
+            signed_distance_errors: BTreeMap::new(),
+            content_scores: AttachmentCounts::default(),
+            projective_scores: AttachmentCounts::default(),
+            non_projective_scores: AttachmentCounts::default(),
+            projective_sentence_scores: AttachmentCounts::default(),
+            non_projective_sentence_scores: AttachmentCounts::default(),
+            pos_scores: BTreeMap::new(),
+            root_prf: RootPrf::default(),
+            target_rel_prf: options
+                .target_rels
+                .iter()
+                .map(|rel| (rel.clone(), RelPrf::default()))
+                .collect(),
+            ignored_root_rels: 0,
+            missing_heads: 0,
+            unsampled_sentences: 0,
+            suppressed_errors: 0,
+            skipped: SkipCounts::default(),
+            confusion_examples: BTreeMap::new(),
+            deprel_confusion,
+            distance_confusion,
+            direction_confusion,
+        }
+    }
+
+    /// Score a sentence pair.
+    fn add_sentence(
+        &mut self,
+        sentence_idx: usize,
+        val_sentence: &Sentence,
+        pred_sentence: &Sentence,
+        options: &EvalOptions,
+        mut logs: ErrorLogs<'_>,
+    ) -> Result<(), Error> {
+        match check_sentence_pair(sentence_idx, val_sentence, pred_sentence, options)? {
+            Some(SentenceMismatch::Length) => {
+                self.skipped.length_mismatch += 1;
+                return Ok(());
+            }
+            Some(SentenceMismatch::Form) => {
+                self.skipped.form_mismatch += 1;
+                return Ok(());
+            }
+            None => (),
+        }
+        let mut sentence_errors = 0;
+        let mut error_tokens = Vec::new();
+        let mut sentence_score = AttachmentCounts::default();
+        let val_non_projective = non_projective_edges(val_sentence);
+        for (idx, (val_token, pred_token)) in val_sentence
+            .iter()
+            .filter_map(|t| t.token())
+            .zip(pred_sentence.iter().filter_map(|t| t.token()))
+            .enumerate() {
+            let idx = idx + 1;
+            let val_triple = val_sentence.dep_graph().head(idx);
+            let pred_triple = pred_sentence.dep_graph().head(idx);
+            let score = match score_token(sentence_idx, idx, val_token, pred_token, val_triple.as_ref(), pred_triple.as_ref(), options)? {
+                TokenDecision::Skipped => continue,
+                TokenDecision::MissingAnnotation => {
+                    self.skipped.missing_annotation += 1;
+                    continue;
+                }
+                TokenDecision::Scored(score) => score,
+            };
+            let TokenScore {
+                val_head,
+                val_rel,
+                pred_head,
+                pred_rel,
+                head_correct,
+                rel_correct,
+                ..
+            } = score;
+            let val_dist = i64::abs(val_head as i64 - idx as i64) as usize;
+            let pred_dist = pred_head.map(|pred_head| i64::abs(pred_head as i64 - idx as i64) as usize);
+            if !score.excluded_from_confusion {
+                if let (Some(pred_head), Some(pred_dist)) = (pred_head, pred_dist) {
+                    self.distance_confusion.insert(
+                        distance_label(val_head, val_dist, options),
+                        distance_label(pred_head, pred_dist, options),
+                    );
+                    self.direction_confusion.insert(Direction::new(idx, val_head), Direction::new(idx, pred_head));
+                }
+
+                self.deprel_confusion.insert(val_rel, pred_rel.unwrap_or("_"));
+                if let Some(max_examples) = options.confusion_examples {
+                    let pred_rel = pred_rel.unwrap_or("_");
+                    if val_rel != pred_rel {
+                        let examples = self
+                            .confusion_examples
+                            .entry((val_rel.to_owned(), pred_rel.to_owned()))
+                            .or_default();
+                        if examples.len() < max_examples {
+                            examples.push(ConfusionExample::new(sentence_idx, idx, val_sentence));
+                        }
+                    }
+                }
+            }
+            if score.excluded {
+                continue
+            }
+
+            self.missing_heads += pred_head.is_none() as usize;
+            if let Some(val_tag) = options.pos_column.tag(val_token) {
+                self.pos_total += 1;
+                self.correct_pos += (options.pos_column.tag(pred_token) == Some(val_tag)) as usize;
+            }
+            self.feats.insert(
+                val_token.features().map(Features::as_str),
+                pred_token.features().map(Features::as_str),
+            );
+            if let Some(val_lemma) = val_token.lemma() {
+                self.lemma_total += 1;
+                self.correct_lemma += (pred_token.lemma() == Some(val_lemma)) as usize;
+            }
+            self.ignored_root_rels += score.ignored_root_rel as usize;
+
+            if !(head_correct && rel_correct) && logs.sentences.is_some() {
+                error_tokens.push(idx);
+            }
+            if let Some(writer) = &mut logs.tokens {
+                if !(head_correct && rel_correct) {
+                    if options.max_errors_per_sentence.map(|max| sentence_errors < max).unwrap_or(true) {
+                        writeln!(
+                            writer,
+                            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                            sentence_idx,
+                            idx,
+                            val_token.form(),
+                            val_head,
+                            val_rel,
+                            pred_head.map(|head| head.to_string()).unwrap_or_else(|| "_".to_owned()),
+                            pred_rel.unwrap_or("_")
+                        )?;
+                    } else {
+                        self.suppressed_errors += 1;
+                    }
+                    sentence_errors += 1;
+                }
+            }
+
+            self.root_prf.insert(val_head == 0, pred_head == Some(0));
+            for (rel, prf) in &mut self.target_rel_prf {
+                prf.insert(val_rel == rel, pred_rel == Some(rel.as_str()), head_correct);
+            }
+
+            sentence_score.insert(head_correct, head_correct && rel_correct);
+            let weight = match &options.weights {
+                Some(weights) => weights.weight(val_token, val_rel, options.pos_column)? as f64,
+                None => 1.,
+            };
+            self.weighted_total += weight;
+            if head_correct {
+                self.weighted_correct_head += weight;
+                if rel_correct {
+                    self.weighted_correct_head_label += weight;
+                }
+            }
+            self.rel_scores
+                .entry(val_rel.to_owned())
+                .or_default()
+                .insert(head_correct, head_correct && rel_correct);
+            let val_direction = Direction::new(idx, val_head);
+            let direction_dist = if val_direction == Direction::Root { 0 } else { val_dist };
+            self.distance_direction_scores
+                .entry((direction_dist, val_direction))
+                .or_default()
+                .insert(head_correct, head_correct && rel_correct);
+            self.pos_scores
+                .entry(options.pos_column.tag(val_token).unwrap_or("_").to_owned())
+                .or_default()
+                .insert(head_correct, head_correct && rel_correct);
+            if is_content_rel(val_rel) {
+                self.content_scores.insert(head_correct, head_correct && rel_correct);
+            }
+            if val_non_projective[idx] {
+                self.non_projective_scores.insert(head_correct, head_correct && rel_correct);
+            } else {
+                self.projective_scores.insert(head_correct, head_correct && rel_correct);
+            }
+            self.correct_label += (pred_rel == Some(val_rel)) as usize;
+            // Edges from the root have no reverse edge.
+            let reverse_correct = val_head != 0
+                && pred_sentence.dep_graph().head(val_head).map(|triple| triple.head()) == Some(idx);
+            self.correct_undirected += (head_correct || reverse_correct) as usize;
+            if let Some(pred_dist) = pred_dist {
+                *self.distance_errors.entry(val_dist.abs_diff(pred_dist)).or_default() += 1;
+                *self.signed_distance_errors.entry(pred_dist as i64 - val_dist as i64).or_default() += 1;
+            }
+        }
+        self.correct_head += sentence_score.correct_head;
+        self.correct_head_label += sentence_score.correct_head_label;
+        self.total += sentence_score.total;
+        if val_non_projective.contains(&true) {
+            self.non_projective_sentence_scores += sentence_score;
+        } else {
+            self.projective_sentence_scores += sentence_score;
+        }
+        self.sentence_scores.push(sentence_score);
+        self.sentence_lengths.push(val_sentence.len() - 1);
+        self.sentence_indices.push(sentence_idx);
+        self.sentences += 1;
+        if let Some(writer) = logs.sentences {
+            if !error_tokens.is_empty() {
+                write_error_sentence(writer, sentence_idx, &error_tokens, val_sentence, pred_sentence)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the counts of the following part of the data.
+    fn merge(&mut self, other: EvalResult) {
+        self.correct_head += other.correct_head;
+        self.correct_head_label += other.correct_head_label;
+        self.total += other.total;
+        self.correct_label += other.correct_label;
+        self.correct_undirected += other.correct_undirected;
+        self.weighted_total += other.weighted_total;
+        self.weighted_correct_head += other.weighted_correct_head;
+        self.weighted_correct_head_label += other.weighted_correct_head_label;
+        self.feats += other.feats;
+        self.pos_total += other.pos_total;
+        self.correct_pos += other.correct_pos;
+        self.lemma_total += other.lemma_total;
+        self.correct_lemma += other.correct_lemma;
+        self.sentences += other.sentences;
+        self.sentence_scores.extend(other.sentence_scores);
+        self.sentence_lengths.extend(other.sentence_lengths);
+        self.sentence_indices.extend(other.sentence_indices);
+        for (rel, score) in other.rel_scores {
+            *self.rel_scores.entry(rel).or_default() += score;
+        }
+        for (key, score) in other.distance_direction_scores {
+            *self.distance_direction_scores.entry(key).or_default() += score;
+        }
+        for (pos, score) in other.pos_scores {
+            *self.pos_scores.entry(pos).or_default() += score;
+        }
+        for (error, count) in other.distance_errors {
+            *self.distance_errors.entry(error).or_default() += count;
+        }
+        for (error, count) in other.signed_distance_errors {
+            *self.signed_distance_errors.entry(error).or_default() += count;
+        }
+        self.content_scores += other.content_scores;
+        self.projective_scores += other.projective_scores;
+        self.non_projective_scores += other.non_projective_scores;
+        self.projective_sentence_scores += other.projective_sentence_scores;
+        self.non_projective_sentence_scores += other.non_projective_sentence_scores;
+        self.root_prf += other.root_prf;
+        for (rel, prf) in other.target_rel_prf {
+            *self.target_rel_prf.entry(rel).or_default() += prf;
+        }
+        self.ignored_root_rels += other.ignored_root_rels;
+        self.missing_heads += other.missing_heads;
+        self.unsampled_sentences += other.unsampled_sentences;
+        self.suppressed_errors += other.suppressed_errors;
+        self.skipped += other.skipped;
+        for (pair, examples) in other.confusion_examples {
+            self.confusion_examples.entry(pair).or_default().extend(examples);
+        }
+        self.deprel_confusion.merge(other.deprel_confusion);
+        self.distance_confusion.merge(other.distance_confusion);
+        self.direction_confusion.merge(other.direction_confusion);
+    }
+
+    /// Unlabeled attachment score.
+    pub fn uas(&self) -> f32 {
+        ratio(self.correct_head, self.total)
+    }
+
+    /// POS accuracy over the scored tokens with a gold tag.
+    pub fn pos_accuracy(&self) -> f32 {
+        ratio(self.correct_pos, self.pos_total)
+    }
+
+    /// Lemma accuracy over the scored tokens with a gold lemma.
+    pub fn lemma_accuracy(&self) -> f32 {
+        ratio(self.correct_lemma, self.lemma_total)
+    }
+
+    /// Undirected unlabeled attachment score.
+    ///
+    /// A token counts as correct if it is attached to its gold head, or
+    /// if its gold head is attached to it.
+    pub fn uuas(&self) -> f32 {
+        ratio(self.correct_undirected, self.total)
+    }
+
+    /// Labeled attachment score.
+    pub fn las(&self) -> f32 {
+        ratio(self.correct_head_label, self.total)
+    }
+
+    /// Unlabeled exact match, the fraction of sentences in which every
+    /// scored token has the correct head.
+    pub fn uem(&self) -> f32 {
+        let matches = self
+            .sentence_scores
+            .iter()
+            .filter(|score| score.correct_head == score.total)
+            .count();
+        ratio(matches, self.sentences)
+    }
+
+    /// Labeled exact match, the fraction of sentences in which every
+    /// scored token has the correct head and relation.
+    pub fn lem(&self) -> f32 {
+        let matches = self
+            .sentence_scores
+            .iter()
+            .filter(|score| score.correct_head_label == score.total)
+            .count();
+        ratio(matches, self.sentences)
+    }
+
+    /// Unlabeled attachment score with tokens weighted by
+    /// `EvalOptions::weights`.
+    pub fn weighted_uas(&self) -> f32 {
+        weighted_ratio(self.weighted_correct_head, self.weighted_total)
+    }
+
+    /// Labeled attachment score with tokens weighted by
+    /// `EvalOptions::weights`.
+    pub fn weighted_las(&self) -> f32 {
+        weighted_ratio(self.weighted_correct_head_label, self.weighted_total)
+    }
+
+    /// Content-word labeled attachment score.
+    pub fn clas(&self) -> f32 {
+        self.content_scores.las()
+    }
+
+    /// Label accuracy.
+    pub fn la(&self) -> f32 {
+        ratio(self.correct_label, self.total)
+    }
+
+    /// Macro-averaged labeled attachment score.
+    ///
+    /// The average of the LAS of every gold relation. Since relations
+    /// are only counted when they occur in the gold data, relations that
+    /// were only predicted do not contribute to the average.
+    pub fn macro_las(&self) -> f32 {
+        let las = self
+            .rel_scores
+            .values()
+            .filter(|counts| counts.total != 0)
+            .map(AttachmentCounts::las)
+            .collect::<Vec<_>>();
+        if las.is_empty() {
+            0.
+        } else {
+            las.iter().sum::<f32>() / las.len() as f32
+        }
+    }
+
+    /// Mean number of tokens of the scored sentences.
+    ///
+    /// Counts all tokens, including tokens that were excluded from the
+    /// attachment scores.
+    pub fn mean_sentence_length(&self) -> f32 {
+        ratio(self.sentence_lengths.iter().sum(), self.sentence_lengths.len())
+    }
+
+    /// Get the attachment counts per sentence label, e.g. per genre.
+    ///
+    /// `labels` holds a label for every input sentence, including
+    /// sentences that were not sampled. Returns the label, the number of
+    /// sentences and the attachment counts of every label in
+    /// lexicographic order.
+    pub fn group_scores(&self, labels: &[String]) -> Vec<(String, usize, AttachmentCounts)> {
+        assert_eq!(
+            labels.len(),
+            self.sentences + self.unsampled_sentences,
+            "Number of labels does not match the number of sentences"
+        );
+        let mut groups = BTreeMap::<&str, (usize, AttachmentCounts)>::new();
+        for (&idx, &score) in self.sentence_indices.iter().zip(&self.sentence_scores) {
+            let (n_sentences, group_score) = groups.entry(&labels[idx]).or_default();
+            *n_sentences += 1;
+            *group_score += score;
+        }
+        groups
+            .into_iter()
+            .map(|(label, (n_sentences, score))| (label.to_owned(), n_sentences, score))
+            .collect()
+    }
+
+    /// Mean absolute difference between predicted and gold head distance.
+    pub fn mean_distance_error(&self) -> f32 {
+        let sum = self.distance_errors.iter().map(|(&error, &count)| error * count).sum();
+        ratio(sum, self.distance_errors.values().sum())
+    }
+
+    /// Median absolute difference between predicted and gold head
+    /// distance.
+    ///
+    /// For an even number of tokens, this is the mean of the two middle
+    /// values.
+    pub fn median_distance_error(&self) -> f32 {
+        let n = self.distance_errors.values().sum::<usize>();
+        if n == 0 {
+            return 0.;
+        }
+        let nth = |idx: usize| {
+            let mut seen = 0;
+            for (&error, &count) in &self.distance_errors {
+                seen += count;
+                if seen > idx {
+                    return error;
+                }
+            }
+            unreachable!()
+        };
+        (nth((n - 1) / 2) + nth(n / 2)) as f32 / 2.
+    }
+
+    /// Fraction of tokens with the gold head distance.
+    pub fn exact_distance(&self) -> f32 {
+        ratio(
+            self.distance_errors.get(&0).copied().unwrap_or(0),
+            self.distance_errors.values().sum(),
+        )
+    }
+
+    /// Get the cumulative attachment counts of corpus prefixes.
+    ///
+    /// `ends` holds the number of scored sentences of every prefix in
+    /// ascending order, ends beyond the number of sentences are ignored.
+    /// Returns the number of sentences and the attachment counts of every
+    /// prefix.
+    pub fn cumulative_scores(&self, ends: &[usize]) -> Vec<(usize, AttachmentCounts)> {
+        let mut prefixes = Vec::with_capacity(ends.len());
+        let mut counts = AttachmentCounts::default();
+        let mut start = 0;
+        for &end in ends.iter().take_while(|&&end| end <= self.sentence_scores.len()) {
+            for &score in &self.sentence_scores[start..end] {
+                counts += score;
+            }
+            start = end;
+            prefixes.push((end, counts));
+        }
+        prefixes
+    }
+
+    /// Group the sentences by their LAS.
+    ///
+    /// The strata are `100%`, `90-99%` to `50-59%` in steps of ten
+    /// percent and `<50%`, followed by `unscored` for sentences without
+    /// scored tokens, e.g. because all their tokens are punctuation with
+    /// `no_punct`. Returns the label and the input indices of the
+    /// sentences (see `sentence_indices`) of every stratum, from the
+    /// highest to the lowest LAS.
+    pub fn las_strata(&self) -> Vec<(String, Vec<usize>)> {
+        let mut strata = vec![Vec::new(); 8];
+        for (&idx, score) in self.sentence_indices.iter().zip(&self.sentence_scores) {
+            let stratum = if score.total == 0 {
+                7
+            } else if score.correct_head_label == score.total {
+                0
+            } else {
+                let percent = score.correct_head_label * 100 / score.total;
+                if percent < 50 { 6 } else { 10 - percent / 10 }
+            };
+            strata[stratum].push(idx);
+        }
+        let labels = ["100%", "90-99%", "80-89%", "70-79%", "60-69%", "50-59%", "<50%", "unscored"];
+        labels.iter().map(|label| label.to_string()).zip(strata).collect()
+    }
+
+    /// Get the histogram of signed head distance errors.
+    ///
+    /// Errors are bucketed from `-max` to `max`, the outer buckets
+    /// `<=-max` and `>=+max` also hold all larger errors. Returns the
+    /// label and the number of tokens of every bucket in ascending order.
+    pub fn distance_error_histogram(&self, max: usize) -> Vec<(String, usize)> {
+        let max = max.max(1) as i64;
+        let mut counts = vec![0; 2 * max as usize + 1];
+        for (&error, &count) in &self.signed_distance_errors {
+            counts[(error.clamp(-max, max) + max) as usize] += count;
+        }
+        (-max..=max)
+            .zip(counts)
+            .map(|(error, count)| {
+                let label = match error {
+                    _ if error == -max => format!("<={}", error),
+                    _ if error == max => format!(">=+{}", error),
+                    0 => "0".to_owned(),
+                    _ if error > 0 => format!("+{}", error),
+                    _ => error.to_string(),
+                };
+                (label, count)
+            })
+            .collect()
+    }
+
+    /// Get the attachment counts per sentence length bucket.
+    ///
+    /// Returns the bucket label, the number of sentences and the
+    /// attachment counts of every bucket in ascending order.
+    pub fn length_buckets(&self, buckets: &Buckets) -> Vec<(String, usize, AttachmentCounts)> {
+        let mut counts = vec![(0, AttachmentCounts::default()); buckets.n_buckets()];
+        for (&len, &score) in self.sentence_lengths.iter().zip(&self.sentence_scores) {
+            let (n_sentences, bucket_score) = &mut counts[buckets.index(len)];
+            *n_sentences += 1;
+            *bucket_score += score;
+        }
+        buckets
+            .labels()
+            .into_iter()
+            .zip(counts)
+            .map(|(label, (n_sentences, score))| (label, n_sentences, score))
+            .collect()
+    }
+
+    /// Get the attachment counts per gold relation frequency bucket.
+    ///
+    /// Relations are bucketed by their number of scored gold tokens.
+    /// Returns the bucket label, the number of relations and the
+    /// attachment counts of every bucket in ascending order.
+    pub fn label_frequency_buckets(&self, buckets: &Buckets) -> Vec<(String, usize, AttachmentCounts)> {
+        let mut counts = vec![(0, AttachmentCounts::default()); buckets.n_buckets()];
+        for score in self.rel_scores.values() {
+            let (n_rels, bucket_score) = &mut counts[buckets.index(score.total)];
+            *n_rels += 1;
+            *bucket_score += *score;
+        }
+        buckets
+            .labels()
+            .into_iter()
+            .zip(counts)
+            .map(|(label, (n_rels, score))| (label, n_rels, score))
+            .collect()
+    }
+
+    /// Get the attachment counts per gold head distance bucket and
+    /// direction.
+    ///
+    /// Exact distances are used if no buckets are given. Tokens attached
+    /// to the root are in a separate `ROOT` bucket, which comes first.
+    /// The other rows are ordered by bucket and direction.
+    pub fn distance_direction_buckets(&self, buckets: Option<&Buckets>) -> Vec<(String, Direction, AttachmentCounts)> {
+        let mut rows: BTreeMap<(usize, Direction), (String, AttachmentCounts)> = BTreeMap::new();
+        for (&(dist, direction), &score) in &self.distance_direction_scores {
+            let (key, label) = match (direction, buckets) {
+                (Direction::Root, _) => (0, ROOT_DISTANCE.to_owned()),
+                (_, Some(buckets)) => (buckets.index(dist) + 1, buckets.label(dist)),
+                (_, None) => (dist, dist.to_string()),
+            };
+            rows.entry((key, direction))
+                .or_insert_with(|| (label, AttachmentCounts::default()))
+                .1 += score;
+        }
+        rows.into_iter()
+            .map(|((_, direction), (label, score))| (label, direction, score))
+            .collect()
+    }
+
+    /// Order the values of the confusion matrices canonically.
+    ///
+    /// Relations are sorted alphabetically, distances numerically with
+    /// `ROOT` first and directions as declared. The other per-label
+    /// outputs are always sorted.
+    pub fn sort_labels(&mut self) {
+        self.deprel_confusion.sort_values();
+        self.distance_confusion.sort_values_by(|a, b| distance_order(a).cmp(&distance_order(b)));
+        self.direction_confusion.sort_values();
+    }
+
+    /// Get the scalar metrics.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            uas: self.uas(),
+            las: self.las(),
+            la: self.la(),
+            uuas: self.uuas(),
+            clas: self.clas(),
+            macro_las: self.macro_las(),
+            uem: self.uem(),
+            lem: self.lem(),
+            pos_accuracy: self.pos_accuracy(),
+            lemma_accuracy: self.lemma_accuracy(),
+            tokens: self.total,
+            sentences: self.sentences,
+            mean_distance_error: self.mean_distance_error(),
+            median_distance_error: self.median_distance_error(),
+            exact_distance: self.exact_distance(),
+            root: RootSummary {
+                precision: self.root_prf.precision(),
+                recall: self.root_prf.recall(),
+                f1: self.root_prf.f1(),
+            },
+        }
+    }
+}
+
+/// The reason for skipping a sentence pair, see `EvalOptions::skip_mismatched`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SentenceMismatch {
+    /// The sentences have a different number of tokens.
+    Length,
+
+    /// The sentences have a token with different forms.
+    Form,
+}
+
+/// Check that a sentence pair can be scored.
+///
+/// Returns the mismatch of a sentence pair that is skipped. Mismatched
+/// sentences are an error unless `skip_mismatched` is set. All forms are
+/// checked before any token is scored, so that a skipped sentence does
+/// not contribute any counts.
+pub(crate) fn check_sentence_pair(
+    sentence_idx: usize,
+    val_sentence: &Sentence,
+    pred_sentence: &Sentence,
+    options: &EvalOptions,
+) -> Result<Option<SentenceMismatch>, Error> {
+    if val_sentence.len() != pred_sentence.len() {
+        if options.skip_mismatched {
+            return Ok(Some(SentenceMismatch::Length));
+        }
+        return Err(format_err!(
+            "Sentence {}: validation has {} tokens, prediction has {} tokens",
+            sentence_idx,
+            val_sentence.len() - 1,
+            pred_sentence.len() - 1
+        ));
+    }
+    if !options.no_form_check {
+        let mismatch = val_sentence
+            .iter()
+            .filter_map(|t| t.token())
+            .zip(pred_sentence.iter().filter_map(|t| t.token()))
+            .enumerate()
+            .find(|(_, (val_token, pred_token))| {
+                !forms_match(val_token.form(), pred_token.form(), options.ignore_case_forms)
+            });
+        if let Some((idx, (val_token, pred_token))) = mismatch {
+            if options.skip_mismatched {
+                return Ok(Some(SentenceMismatch::Form));
+            }
+            return Err(format_err!(
+                "Sentence {}, token {}: validation form '{}' differs from prediction form '{}'",
+                sentence_idx,
+                idx + 1,
+                val_token.form(),
+                pred_token.form()
+            ));
+        }
+    }
+    Ok(None)
+}
+
+/// The scoring decision of a token, see `score_token`.
+pub(crate) enum TokenDecision<'a> {
+    /// The token is not scored at all, see `skip_punctuation`.
+    Skipped,
+
+    /// The validation token has no head or relation and is skipped, see
+    /// `skip_mismatched`.
+    MissingAnnotation,
+
+    /// The token is scored.
+    Scored(TokenScore<'a>),
+}
+
+/// The attachment of a scored token.
+///
+/// Predicted relations are derived with `clause_relations` if set, relations
+/// are normalized with `rel_rules` and `coarse_rels`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TokenScore<'a> {
+    pub val_head: usize,
+
+    pub val_rel: &'a str,
+
+    /// The predicted head, `None` if the prediction has no head.
+    pub pred_head: Option<usize>,
+
+    /// The predicted relation, `None` if the prediction has no relation.
+    pub pred_rel: Option<&'a str>,
+
+    /// The token is excluded from the attachment scores, see `no_punct`
+    /// and `ignore_rels`.
+    pub excluded: bool,
+
+    /// The token is excluded from the confusion matrices as well.
+    pub excluded_from_confusion: bool,
+
+    pub head_correct: bool,
+
+    /// The relation is correct. A missing predicted relation is wrong.
+    pub rel_correct: bool,
+
+    /// The relation is counted as correct because of `ignore_root_rel`.
+    pub ignored_root_rel: bool,
+}
+
+/// Decide how the token with index `idx` is scored.
+///
+/// This is the decision of `Scorer` for every token, so that other
+/// evaluations apply the same options. Missing validation heads or
+/// relations are an error unless `skip_mismatched` is set.
+pub(crate) fn score_token<'a>(
+    sentence_idx: usize,
+    idx: usize,
+    val_token: &Token,
+    pred_token: &Token,
+    val_triple: Option<&'a DepTriple<&str>>,
+    pred_triple: Option<&'a DepTriple<&str>>,
+    options: &'a EvalOptions,
+) -> Result<TokenDecision<'a>, Error> {
+    if options.skip_punctuation && val_token.pos().expect("Validation token missing POS").starts_with("PUNCT") {
+        return Ok(TokenDecision::Skipped);
+    }
+    let val_triple = match val_triple {
+        Some(triple) if triple.relation().is_some() => triple,
+        _ if options.skip_mismatched => return Ok(TokenDecision::MissingAnnotation),
+        _ => {
+            return Err(format_err!(
+                "Sentence {}, token {}: validation token has no head or relation",
+                sentence_idx,
+                idx
+            ))
+        }
+    };
+    let val_head = val_triple.head();
+    let mut val_rel = val_triple.relation().unwrap();
+    // Missing predicted heads and relations are attachment errors.
+    let pred_head = pred_triple.map(DepTriple::head);
+    let mut pred_rel = pred_triple.and_then(DepTriple::relation);
+    if let Some(relations) = &options.clause_relations {
+        pred_rel = Some(relations.relation(pred_token));
+    }
+    let excluded_punct = options.no_punct && is_punct(val_token, val_rel, &options.punct_tags);
+    let excluded_rel = options.ignore_rels.contains(val_rel);
+    if let Some(rules) = &options.rel_rules {
+        val_rel = rules.apply(val_rel);
+        pred_rel = pred_rel.map(|rel| rules.apply(rel));
+    }
+    if options.coarse_rels {
+        val_rel = strip_subtype(val_rel);
+        pred_rel = pred_rel.map(strip_subtype);
+    }
+    let head_correct = pred_head == Some(val_head);
+    let rel_correct = pred_rel == Some(val_rel);
+    let ignored_root_rel = options.ignore_root_rel && val_head == 0 && head_correct && !rel_correct;
+    Ok(TokenDecision::Scored(TokenScore {
+        val_head,
+        val_rel,
+        pred_head,
+        pred_rel,
+        excluded: excluded_punct || excluded_rel,
+        excluded_from_confusion: excluded_rel || excluded_punct && options.no_punct_confusion,
+        head_correct,
+        rel_correct: rel_correct || ignored_root_rel,
+        ignored_root_rel,
+    }))
+}
+
+/// Selection of the scored sentences by their input position.
+///
+/// See `sentence_range`, `skip`, `max_sentences` and `sample_rate`.
+pub(crate) struct SentenceSelection {
+    sentence_range: Option<(usize, usize)>,
+    first_sentence: usize,
+    end: Option<usize>,
+    sample_rate: Option<f64>,
+    rng: SplitMix64,
+}
+
+impl SentenceSelection {
+    pub(crate) fn new(options: &EvalOptions) -> Self {
+        let (range_start, range_end) = match options.sentence_range {
+            Some((start, end)) => (start, Some(end)),
+            None => (0, None),
+        };
+        let max_end = options.max_sentences.map(|max| options.skip + max);
+        SentenceSelection {
+            sentence_range: options.sentence_range,
+            first_sentence: range_start.max(options.skip),
+            end: match (range_end, max_end) {
+                (Some(range_end), Some(max_end)) => Some(range_end.min(max_end)),
+                (range_end, max_end) => range_end.or(max_end),
+            },
+            sample_rate: options.sample_rate,
+            rng: SplitMix64::new(options.sample_seed),
+        }
+    }
+
+    /// Check whether no sentence from position `sentence_idx` on is
+    /// selected, so that reading can stop.
+    pub(crate) fn done(&self, sentence_idx: usize) -> bool {
+        self.end.map(|end| sentence_idx >= end).unwrap_or(false)
+    }
+
+    /// Check whether the sentence at `sentence_idx` precedes the
+    /// selected sentences.
+    pub(crate) fn skip(&self, sentence_idx: usize) -> bool {
+        sentence_idx < self.first_sentence
+    }
+
+    /// Draw whether the next sentence is part of the sample.
+    ///
+    /// Must be called once for every sentence that is not skipped.
+    pub(crate) fn sample(&mut self) -> bool {
+        let rng = &mut self.rng;
+        self.sample_rate.map(|rate| rng.next_f64() < rate).unwrap_or(true)
+    }
+
+    /// Check that the input has the sentences of the sentence range, given
+    /// the number of sentences read.
+    pub(crate) fn check_range(&self, n_read: usize) -> Result<(), Error> {
+        match self.sentence_range {
+            Some((start, end)) if n_read < end => Err(format_err!(
+                "Sentence range {}:{} exceeds the {} sentences of the input",
+                start,
+                end,
+                n_read
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Evaluate predicted sentences against validation sentences.
+///
+/// Every token is scored, see `evaluate_with` for options.
+pub fn evaluate(val: impl ReadSentence, pred: impl ReadSentence) -> Result<EvalResult, Error> {
+    evaluate_with(val, pred, &EvalOptions::default(), &mut EvalWriters::default())
+}
+
+/// Evaluate predicted sentences against validation sentences.
+pub fn evaluate_with(
+    mut val_reader: impl ReadSentence,
+    mut pred_reader: impl ReadSentence,
+    options: &EvalOptions,
+    writers: &mut EvalWriters,
+) -> Result<EvalResult, Error> {
+    let mut scorer = Scorer::with_options(options.clone());
+
+    let mut selection = SentenceSelection::new(options);
+    let mut sentences = Vec::new();
+    let mut n_read = 0;
+    loop {
+        if selection.done(n_read) {
+            break;
+        }
+        let sentence_idx = n_read;
+        let (val_sentence, pred_sentence) = match (val_reader.read_sentence()?, pred_reader.read_sentence()?) {
+            (Some(val_sentence), Some(pred_sentence)) => (val_sentence, pred_sentence),
+            (None, None) => break,
+            (val_sentence, pred_sentence) => {
+                let val_count = n_read + val_sentence.is_some() as usize + count_sentences(val_reader)?.0;
+                let pred_count = n_read + pred_sentence.is_some() as usize + count_sentences(pred_reader)?.0;
+                return Err(format_err!(
+                    "Validation has {} sentences, prediction has {} sentences",
+                    val_count,
+                    pred_count
+                ));
+            }
+        };
+        n_read += 1;
+        if selection.skip(sentence_idx) {
+            continue;
+        }
+        if !selection.sample() {
+            scorer.result.unsampled_sentences += 1;
+        } else {
+            if let Some(writer) = &mut writers.merged {
+                write_merged(writer, &val_sentence, &pred_sentence)?;
+            }
+            if options.threads > 1 {
+                sentences.push((sentence_idx, val_sentence, pred_sentence));
+            } else {
+                let logs = ErrorLogs {
+                    tokens: writers.errors.as_mut().map(|w| &mut **w as &mut dyn Write),
+                    sentences: writers.error_sentences.as_mut().map(|w| &mut **w as &mut dyn Write),
+                };
+                scorer.push_at(sentence_idx, &val_sentence, &pred_sentence, logs)?;
+            }
+        }
+        if let Some(interval) = options.progress {
+            if n_read.is_multiple_of(interval) {
+                report_progress(&scorer.result, n_read, options);
+            }
+        }
+    }
+    selection.check_range(n_read)?;
+
+    if !sentences.is_empty() {
+        let log_errors = writers.errors.is_some();
+        let log_error_sentences = writers.error_sentences.is_some();
+        let chunk_size = sentences.len().div_ceil(options.threads);
+        let partials = thread::scope(|scope| {
+            let handles = sentences
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || -> Result<(EvalResult, Vec<u8>, Vec<u8>), Error> {
+                        let mut partial = Scorer::with_options(options.clone());
+                        let mut errors = Vec::new();
+                        let mut error_sentences = Vec::new();
+                        for (sentence_idx, val_sentence, pred_sentence) in chunk {
+                            let logs = ErrorLogs {
+                                tokens: if log_errors { Some(&mut errors) } else { None },
+                                sentences: if log_error_sentences { Some(&mut error_sentences) } else { None },
+                            };
+                            partial.push_at(*sentence_idx, val_sentence, pred_sentence, logs)?;
+                        }
+                        Ok((partial.finish(), errors, error_sentences))
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Scoring thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        for partial in partials {
+            let (partial, errors, error_sentences) = partial?;
+            if let Some(writer) = &mut writers.errors {
+                writer.write_all(&errors)?;
+            }
+            if let Some(writer) = &mut writers.error_sentences {
+                writer.write_all(&error_sentences)?;
+            }
+            scorer.result.merge(partial);
+        }
+        if let Some(max_examples) = options.confusion_examples {
+            for examples in scorer.result.confusion_examples.values_mut() {
+                examples.truncate(max_examples);
+            }
+        }
+    }
+
+    let mut result = scorer.finish();
+    if options.sort_labels {
+        result.sort_labels();
+    }
+    Ok(result)
+}
+
+/// Report the number of sentences read and the UAS so far to standard
+/// error.
+///
+/// With more than one thread, sentences are only scored after reading,
+/// so only the number of sentences is reported.
+fn report_progress(result: &EvalResult, n_sentences: usize, options: &EvalOptions) {
+    if options.threads > 1 {
+        eprintln!("Read {} sentences", n_sentences);
+    } else {
+        eprintln!(
+            "Read {} sentences, UAS: {}",
+            n_sentences,
+            options
+                .rounding
+                .format(result.uas(), options.precision.unwrap_or(DEFAULT_PRECISION))
+        );
+    }
+}
+
+/// Incremental scorer of sentence pairs.
+///
+/// Sentence pairs are scored as they are pushed, the scores so far can
+/// be read at any time with `result`.
+pub struct Scorer {
+    options: EvalOptions,
+    result: EvalResult,
+
+    /// Input position of the next sentence pair.
+    ///
+    /// Skipped sentences are not counted in `result.sentences`, so the
+    /// position is tracked separately.
+    next_sentence: usize,
+}
+
+impl Default for Scorer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scorer {
+    /// Construct a scorer that scores every token.
+    pub fn new() -> Self {
+        Self::with_options(EvalOptions::default())
+    }
+
+    /// Construct a scorer with evaluation options.
+    pub fn with_options(options: EvalOptions) -> Self {
+        Scorer {
+            result: EvalResult::empty(&options),
+            options,
+            next_sentence: 0,
+        }
+    }
+
+    /// Score a gold and a predicted sentence.
+    pub fn push(&mut self, gold: &Sentence, pred: &Sentence) -> Result<(), Error> {
+        self.push_at(self.next_sentence, gold, pred, ErrorLogs::default())
+    }
+
+    /// Score a gold and a predicted sentence and log its errors.
+    ///
+    /// See `EvalWriters::errors` for the format of the error log.
+    pub fn push_with_errors(&mut self, gold: &Sentence, pred: &Sentence, errors: &mut dyn Write) -> Result<(), Error> {
+        let logs = ErrorLogs {
+            tokens: Some(errors),
+            sentences: None,
+        };
+        self.push_at(self.next_sentence, gold, pred, logs)
+    }
+
+    /// Score a sentence pair that has the index `sentence_idx` in error
+    /// messages and the error logs.
+    fn push_at(
+        &mut self,
+        sentence_idx: usize,
+        gold: &Sentence,
+        pred: &Sentence,
+        logs: ErrorLogs<'_>,
+    ) -> Result<(), Error> {
+        self.next_sentence = sentence_idx + 1;
+        self.result.add_sentence(sentence_idx, gold, pred, &self.options, logs)
+    }
+
+    /// Get the scores of the sentences pushed so far.
+    pub fn result(&self) -> &EvalResult {
+        &self.result
+    }
+
+    /// Finish scoring and get the scores.
+    pub fn finish(self) -> EvalResult {
+        self.result
+    }
+}
+
+/// Distance label of tokens attached to the root.
+static ROOT_DISTANCE: &str = "ROOT";
+
+/// Relation of tokens attached to the root.
+static ROOT_REL: &str = "root";
+
+/// Universal Dependencies relations of function words.
+static FUNCTIONAL_RELS: &[&str] = &["aux", "case", "cc", "clf", "cop", "det", "mark", "punct"];
+
+/// Check whether a relation attaches a content word.
+///
+/// Subtypes are ignored, so `aux:pass` is functional as well.
+fn is_content_rel(rel: &str) -> bool {
+    !FUNCTIONAL_RELS.contains(&strip_subtype(rel))
+}
+
+/// Get for every node whether its incoming edge is non-projective.
+///
+/// An edge is non-projective if its head does not dominate every
+/// token between the dependent and the head. Since the root dominates
+/// all tokens, edges from the root are always projective.
+fn non_projective_edges(sentence: &Sentence) -> Vec<bool> {
+    let graph = sentence.dep_graph();
+    let heads = (0..sentence.len())
+        .map(|idx| graph.head(idx).map(|triple| triple.head()))
+        .collect::<Vec<_>>();
+
+    let dominates = |head: usize, mut idx: usize| {
+        // Bound the number of steps, malformed graphs may have cycles.
+        for _ in 0..heads.len() {
+            match heads[idx] {
+                Some(parent) if parent == head => return true,
+                Some(parent) => idx = parent,
+                None => return false,
+            }
+        }
+        false
+    };
+
+    heads
+        .iter()
+        .enumerate()
+        .map(|(dependent, head)| match *head {
+            Some(head) => {
+                let (start, end) = if dependent < head { (dependent, head) } else { (head, dependent) };
+                (start + 1..end).any(|inner| !dominates(head, inner))
+            }
+            None => false,
+        })
+        .collect()
+}
+
+/// Label of a head distance in the distance confusion matrix.
+fn distance_label(head: usize, dist: usize, options: &EvalOptions) -> String {
+    if head == 0 {
+        return ROOT_DISTANCE.to_owned();
+    }
+    match &options.distance_buckets {
+        Some(buckets) => buckets.label(dist),
+        None => dist.to_string(),
+    }
+}
+
+/// Sort key of a distance label, `ROOT` first and then by the (lower)
+/// distance of the label.
+fn distance_order(label: &str) -> (bool, usize, &str) {
+    let digits = label.find(|c: char| !c.is_ascii_digit()).unwrap_or(label.len());
+    (label != ROOT_DISTANCE, label[..digits].parse().unwrap_or(usize::MAX), label)
+}
+
+/// Count the sentences and tokens read by a reader.
+pub fn count_sentences(reader: impl ReadSentence) -> Result<(usize, usize), Error> {
+    let mut n_sentences = 0;
+    let mut n_tokens = 0;
+    for sentence in reader.sentences() {
+        n_sentences += 1;
+        n_tokens += sentence?.len() - 1;
+    }
+    Ok((n_sentences, n_tokens))
+}
+
+/// Check whether a validation and a predicted form match.
+///
+/// Forms are compared case-insensitively if `ignore_case` is set.
+fn forms_match(val_form: &str, pred_form: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        val_form.to_lowercase() == pred_form.to_lowercase()
+    } else {
+        val_form == pred_form
+    }
+}
+
+/// Check whether a gold token is punctuation.
+///
+/// A token is punctuation if its relation is `punct` or if its CPOS or POS
+/// tag is one of `punct_tags`.
+fn is_punct(token: &Token, rel: &str, punct_tags: &HashSet<String>) -> bool {
+    rel == "punct"
+        || token.cpos().map(|tag| punct_tags.contains(tag)).unwrap_or(false)
+        || token.pos().map(|tag| punct_tags.contains(tag)).unwrap_or(false)
+}
+
+/// Write a gold sentence as CoNLL-U with the predicted dependencies in MISC.
+///
+/// HEAD and DEPREL hold the gold annotation, the predicted head and
+/// relation are stored as `pred_head=HEAD|pred_rel=REL` in the MISC column.
+/// Missing values are written as `_`. DEPS is left empty.
+fn write_merged(mut w: impl Write, val_sentence: &Sentence, pred_sentence: &Sentence) -> Result<(), Error> {
+    let val_graph = val_sentence.dep_graph();
+    let pred_graph = pred_sentence.dep_graph();
+    for idx in 1..val_sentence.len() {
+        let token = val_sentence[idx].token().expect("Non-root node without token");
+        let (val_head, val_rel) = head_rel_strings(val_graph.head(idx));
+        let (pred_head, pred_rel) = head_rel_strings(pred_graph.head(idx));
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t_\tpred_head={}|pred_rel={}",
+            idx,
+            token.form(),
+            token.lemma().unwrap_or("_"),
+            token.cpos().unwrap_or("_"),
+            token.pos().unwrap_or("_"),
+            token.features().map(Features::as_str).unwrap_or("_"),
+            val_head,
+            val_rel,
+            pred_head,
+            pred_rel,
+        )?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn weighted_ratio(numerator: f64, denominator: f64) -> f32 {
+    if denominator == 0. {
+        0.
+    } else {
+        (numerator / denominator) as f32
+    }
+}
+
+/// Write a sentence with attachment errors.
+///
+/// The sentence is preceded by `# sentence = INDEX` and
+/// `# error_tokens = TOKEN,...` comments and written as by
+/// `write_merged`.
+fn write_error_sentence(
+    mut w: impl Write,
+    sentence_idx: usize,
+    error_tokens: &[usize],
+    val_sentence: &Sentence,
+    pred_sentence: &Sentence,
+) -> Result<(), Error> {
+    writeln!(w, "# sentence = {}", sentence_idx)?;
+    writeln!(w, "# error_tokens = {}", error_tokens.iter().join(","))?;
+    write_merged(w, val_sentence, pred_sentence)
+}
+
+fn head_rel_strings(triple: Option<DepTriple<&str>>) -> (String, String) {
+    match triple {
+        Some(triple) => (
+            triple.head().to_string(),
+            triple.relation().unwrap_or("_").to_owned(),
+        ),
+        None => ("_".to_owned(), "_".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use conllx::graph::Sentence;
+    use conllx::io::ReadSentence;
+
+    use super::{evaluate, evaluate_with, non_projective_edges, EvalOptions, EvalWriters, PosColumn, Scorer};
+    use crate::conllu::Reader;
+
+    static GOLD: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tnsubj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t3\tpunct\t_\t_
+
+1\tCats\tcat\tNOUN\tNNS\t_\t2\tnsubj\t_\t_
+2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    /// The relation of `dog` and the head of `.` are wrong.
+    static PRED: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tobj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t2\tpunct\t_\t_
+
+1\tCats\tcat\tNOUN\tNNS\t_\t2\tnsubj\t_\t_
+2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    fn reader(conllu: &str) -> Reader<&[u8]> {
+        Reader::new(conllu.as_bytes())
+    }
+
+    fn sentences(conllu: &str) -> Vec<Sentence> {
+        let mut reader = reader(conllu);
+        let mut sentences = Vec::new();
+        while let Some(sentence) = reader.read_sentence().unwrap() {
+            sentences.push(sentence);
+        }
+        sentences
+    }
+
+    fn evaluate_options(options: &EvalOptions) -> super::EvalResult {
+        evaluate_with(reader(GOLD), reader(PRED), options, &mut EvalWriters::default()).unwrap()
+    }
+
+    #[test]
+    fn attachment_scores() {
+        let result = evaluate(reader(GOLD), reader(PRED)).unwrap();
+        assert_eq!(result.total, 6);
+        assert_eq!(result.sentences, 2);
+        assert_eq!(result.correct_head, 5);
+        assert_eq!(result.correct_head_label, 4);
+        assert_eq!(result.correct_label, 5);
+    }
+
+    #[test]
+    fn no_punct_and_ignore_rels() {
+        let result = evaluate_options(&EvalOptions {
+            no_punct: true,
+            ..EvalOptions::default()
+        });
+        assert_eq!((result.total, result.correct_head, result.correct_head_label), (5, 5, 4));
+
+        let result = evaluate_options(&EvalOptions {
+            ignore_rels: vec!["nsubj".to_owned()].into_iter().collect(),
+            ..EvalOptions::default()
+        });
+        assert_eq!((result.total, result.correct_head, result.correct_head_label), (4, 3, 3));
+    }
+
+    #[test]
+    fn pos_scores_use_the_pos_column() {
+        let tags = |result: super::EvalResult| result.pos_scores.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(tags(evaluate_options(&EvalOptions::default())), vec![".", "DT", "NN", "NNS", "VBP", "VBZ"]);
+        let result = evaluate_options(&EvalOptions {
+            pos_column: PosColumn::Upos,
+            ..EvalOptions::default()
+        });
+        assert_eq!(result.pos_scores["NOUN"].total, 2);
+        assert_eq!(tags(result), vec!["DET", "NOUN", "PUNCT", "VERB"]);
+    }
+
+    #[test]
+    fn las_strata() {
+        let strata = evaluate_options(&EvalOptions::default()).las_strata();
+        assert_eq!(strata[0], ("100%".to_owned(), vec![1]));
+        assert_eq!(strata[5], ("50-59%".to_owned(), vec![0]));
+
+        // Only the determiner and the period of the first sentence are scored.
+        let result = evaluate_options(&EvalOptions {
+            ignore_rels: vec!["nsubj".to_owned(), "root".to_owned()].into_iter().collect(),
+            ..EvalOptions::default()
+        });
+        let strata = result.las_strata();
+        assert_eq!(strata[0].1, Vec::<usize>::new());
+        assert_eq!(strata[5].1, vec![0]);
+        assert_eq!(strata[7], ("unscored".to_owned(), vec![1]));
+    }
+
+    #[test]
+    fn scorer_indices_count_skipped_sentences() {
+        let gold = sentences(GOLD);
+        let pred = sentences(PRED);
+        let mut scorer = Scorer::with_options(EvalOptions {
+            skip_mismatched: true,
+            ..EvalOptions::default()
+        });
+        let mut errors = Vec::new();
+        // Different lengths, the sentence pair is skipped.
+        scorer.push_with_errors(&gold[0], &pred[1], &mut errors).unwrap();
+        scorer.push_with_errors(&gold[0], &pred[0], &mut errors).unwrap();
+        let result = scorer.finish();
+        assert_eq!(result.skipped.length_mismatch, 1);
+        assert_eq!(result.sentence_indices, vec![1]);
+        let errors = String::from_utf8(errors).unwrap();
+        assert_eq!(errors.lines().next(), Some("1\t2\tdog\t3\tnsubj\t3\tobj"));
+    }
+
+    #[test]
+    fn non_projective_edge() {
+        // The edge from D to B crosses the edge from A to C.
+        let sentence = &sentences(
+            "\
+1\tA\ta\tX\tX\t_\t0\troot\t_\t_
+2\tB\tb\tX\tX\t_\t4\tdep\t_\t_
+3\tC\tc\tX\tX\t_\t1\tdep\t_\t_
+4\tD\td\tX\tX\t_\t1\tdep\t_\t_
+",
+        )[0];
+        assert_eq!(non_projective_edges(sentence), vec![false, false, true, false, false]);
+        assert_eq!(non_projective_edges(&sentences(GOLD)[0]), vec![false; 5]);
+    }
+
+    #[test]
+    fn sentence_range() {
+        let result = evaluate_options(&EvalOptions {
+            sentence_range: Some((1, 2)),
+            ..EvalOptions::default()
+        });
+        assert_eq!(result.sentences, 1);
+        assert_eq!(result.sentence_indices, vec![1]);
+        assert_eq!((result.total, result.correct_head_label), (2, 2));
+
+        let options = EvalOptions {
+            sentence_range: Some((1, 3)),
+            ..EvalOptions::default()
+        };
+        assert!(evaluate_with(reader(GOLD), reader(PRED), &options, &mut EvalWriters::default()).is_err());
+    }
+
+    #[test]
+    fn threads_give_the_same_scores() {
+        let single = evaluate_options(&EvalOptions::default());
+        let threaded = evaluate_options(&EvalOptions {
+            threads: 2,
+            ..EvalOptions::default()
+        });
+        assert_eq!(threaded.sentence_scores, single.sentence_scores);
+        assert_eq!(threaded.sentence_indices, single.sentence_indices);
+        assert_eq!(threaded.rel_scores, single.rel_scores);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::AddAssign;
+
+use failure::Error;
+
+use crate::metrics::{ratio, Rounding};
+
+/// Parse a feature string into a feature map.
+///
+/// Features are separated by `|`. Both CoNLL-U (`key=value`) and
+/// `conllx` (`key:value`) features are accepted. Features without a
+/// value map to an empty string.
+pub fn parse_features(features: &str) -> BTreeMap<&str, &str> {
+    features
+        .split('|')
+        .filter(|feature| !feature.is_empty())
+        .map(|feature| {
+            let mut parts = feature.splitn(2, ['=', ':']);
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        })
+        .collect()
+}
+
+/// Counts of a single feature.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FeatureCounts {
+    /// Number of tokens with the feature in the gold data.
+    pub gold: usize,
+
+    /// Number of tokens with the feature in the prediction.
+    pub predicted: usize,
+
+    /// Number of tokens with the feature and the gold value in the
+    /// prediction.
+    pub correct: usize,
+}
+
+impl FeatureCounts {
+    pub fn precision(&self) -> f32 {
+        ratio(self.correct, self.predicted)
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(self.correct, self.gold)
+    }
+}
+
+impl AddAssign for FeatureCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.gold += other.gold;
+        self.predicted += other.predicted;
+        self.correct += other.correct;
+    }
+}
+
+/// Morphological feature counts.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeatsCounts {
+    /// Number of tokens.
+    pub total: usize,
+
+    /// Number of tokens whose predicted feature map equals the gold map.
+    pub exact: usize,
+
+    /// Counts per feature name.
+    pub features: BTreeMap<String, FeatureCounts>,
+}
+
+impl FeatsCounts {
+    /// Add the gold and predicted features of a token.
+    ///
+    /// A feature that is in the gold data but not in the prediction is a
+    /// miss.
+    pub fn insert(&mut self, gold: Option<&str>, pred: Option<&str>) {
+        let gold = gold.map(parse_features).unwrap_or_default();
+        let pred = pred.map(parse_features).unwrap_or_default();
+
+        self.total += 1;
+        self.exact += (gold == pred) as usize;
+        for (&name, &value) in &gold {
+            let counts = self.features.entry(name.to_owned()).or_default();
+            counts.gold += 1;
+            counts.correct += (pred.get(name) == Some(&value)) as usize;
+        }
+        for &name in pred.keys() {
+            self.features.entry(name.to_owned()).or_default().predicted += 1;
+        }
+    }
+
+    /// Fraction of tokens whose predicted feature map equals the gold map.
+    pub fn exact_match(&self) -> f32 {
+        ratio(self.exact, self.total)
+    }
+
+    /// Write counts, precision and recall per feature as TSV with a
+    /// header row.
+    ///
+    /// The table ends with an `exact` row holding the number of tokens,
+    /// the number of exact matches and the exact match rate.
+    pub fn write(&self, mut w: impl Write, rounding: Rounding, precision: usize) -> Result<(), Error> {
+        writeln!(w, "feature\tgold\tpredicted\tcorrect\tprecision\trecall")?;
+        for (name, counts) in &self.features {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                name,
+                counts.gold,
+                counts.predicted,
+                counts.correct,
+                rounding.format(counts.precision(), precision),
+                rounding.format(counts.recall(), precision)
+            )?;
+        }
+        let exact_match = rounding.format(self.exact_match(), precision);
+        writeln!(
+            w,
+            "exact\t{}\t{}\t{}\t{}\t{}",
+            self.total, self.total, self.exact, exact_match, exact_match
+        )?;
+        Ok(())
+    }
+}
+
+impl AddAssign for FeatsCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.total += other.total;
+        self.exact += other.exact;
+        for (name, counts) in other.features {
+            *self.features.entry(name).or_default() += counts;
+        }
+    }
+}
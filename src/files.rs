@@ -0,0 +1,114 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+
+/// Pair the files of a validation and a prediction directory by name.
+///
+/// Either path can also be a file name pattern in a directory, such as
+/// `gold/*.conllu`, to pair only the matching files, see
+/// `is_file_pattern`. Pairs are sorted by file name. Every file in one
+/// directory must have a counterpart with the same name in the other
+/// directory.
+pub fn pair_files(
+    val_path: impl AsRef<Path>,
+    pred_path: impl AsRef<Path>,
+) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+    let (val_dir, val_pattern) = split_pattern(val_path.as_ref());
+    let (pred_dir, pred_pattern) = split_pattern(pred_path.as_ref());
+    let val_names = file_names(val_dir, val_pattern)?;
+    let pred_names = file_names(pred_dir, pred_pattern)?;
+
+    if let Some(name) = val_names.difference(&pred_names).next() {
+        return Err(format_err!("Missing prediction file: {}", pred_dir.join(name).display()));
+    }
+    if let Some(name) = pred_names.difference(&val_names).next() {
+        return Err(format_err!("Missing validation file: {}", val_dir.join(name).display()));
+    }
+
+    Ok(val_names
+        .into_iter()
+        .map(|name| (val_dir.join(&name), pred_dir.join(&name)))
+        .collect())
+}
+
+/// Check whether the file name of `path` is a pattern.
+///
+/// In a pattern, `*` matches any sequence of characters and `?` matches
+/// a single character. Patterns are only supported in the file name,
+/// not in the directories of the path.
+pub fn is_file_pattern(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .file_name()
+        .map(|name| name.to_string_lossy().contains(['*', '?']))
+        .unwrap_or(false)
+}
+
+/// Split a path into its directory and file name pattern, if any.
+fn split_pattern(path: &Path) -> (&Path, Option<String>) {
+    if !is_file_pattern(path) {
+        return (path, None);
+    }
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    (dir, path.file_name().map(|name| name.to_string_lossy().into_owned()))
+}
+
+fn file_names(dir: &Path, pattern: Option<String>) -> Result<BTreeSet<String>, Error> {
+    let pattern = pattern.map(|pattern| pattern.chars().collect::<Vec<_>>());
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let matched = match &pattern {
+            Some(pattern) => matches_pattern(pattern, &name.chars().collect::<Vec<_>>()),
+            None => true,
+        };
+        if matched && entry.file_type()?.is_file() {
+            names.insert(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Match a name against a pattern with `*` and `?` wildcards.
+fn matches_pattern(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_pattern(rest, &name[skip..])),
+        Some((&c, rest)) => match name.split_first() {
+            Some((&n, name_rest)) => (c == '?' || c == n) && matches_pattern(rest, name_rest),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_file_pattern, matches_pattern};
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        matches_pattern(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.conllu", "dev.conllu"));
+        assert!(matches("*.conllu", ".conllu"));
+        assert!(!matches("*.conllu", "dev.conllu.gz"));
+        assert!(matches("dev-?.*", "dev-1.conllu"));
+        assert!(!matches("dev-?.*", "dev-10.conllu"));
+        assert!(matches("*-*", "a-b-c"));
+    }
+
+    #[test]
+    fn only_file_names_are_patterns() {
+        assert!(is_file_pattern("gold/*.conllu"));
+        assert!(is_file_pattern("*.conllu"));
+        assert!(!is_file_pattern("gold/dev.conllu"));
+        assert!(!is_file_pattern("gold"));
+    }
+}
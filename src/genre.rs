@@ -0,0 +1,37 @@
+use std::io::BufRead;
+
+use conllx::graph::Sentence;
+use failure::Error;
+
+use crate::conllu::{Metadata, Reader};
+use crate::feats::parse_features;
+
+/// Genre of sentences without a genre.
+pub static UNKNOWN_GENRE: &str = "unknown";
+
+/// Read the genre of every sentence.
+///
+/// The genre is the value of the `# key = value` comment of a sentence.
+/// Sentences without such a comment use the `key` feature of their first
+/// token that has this feature. Sentences without either get the genre
+/// `unknown`.
+pub fn read_genres<R>(reader: &mut Reader<R>, key: &str) -> Result<Vec<String>, Error>
+where
+    R: BufRead,
+{
+    let mut genres = Vec::new();
+    while let Some((metadata, sentence)) = reader.read_sentence_with_metadata()? {
+        genres.push(genre(&metadata, &sentence, key).unwrap_or(UNKNOWN_GENRE).to_owned());
+    }
+    Ok(genres)
+}
+
+fn genre<'a>(metadata: &'a Metadata, sentence: &'a Sentence, key: &str) -> Option<&'a str> {
+    if let Some(genre) = metadata.get(key) {
+        return Some(genre);
+    }
+    sentence
+        .iter()
+        .filter_map(|node| node.token())
+        .find_map(|token| parse_features(token.features()?.as_str()).get(key).copied())
+}
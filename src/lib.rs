@@ -0,0 +1,134 @@
+//! Evaluation of dependency parses.
+//!
+//! The central entry point is `evaluate`, which scores predicted
+//! sentences against validation sentences and returns the attachment
+//! scores together with the relation and distance confusion matrices.
+
+use std::collections::VecDeque;
+
+use conllx::graph::Sentence;
+use conllx::io::ReadSentence;
+use conllx::token::Token;
+use failure::Error;
+
+mod align;
+pub use crate::align::{align_by_sent_id, Alignment};
+
+mod bootstrap;
+pub use crate::bootstrap::{bootstrap, paired_bootstrap, BootstrapCi, Interval, PairedTest};
+
+mod browse;
+pub use crate::browse::{browse, read_error_sentences, render_tree, write_side_by_side, ErrorKind, ErrorSentence};
+
+mod bucket;
+pub use crate::bucket::Buckets;
+
+mod clause_ids;
+pub use crate::clause_ids::{ClauseRelations, CLAUSE_ID_FEATURE};
+
+mod confusion;
+pub use crate::confusion::{Confusion, Numberer};
+
+pub mod conllu;
+
+mod eval;
+pub use crate::eval::{
+    count_sentences, evaluate, evaluate_with, ConfusionExample, Direction, EvalOptions, EvalResult, EvalWriters, PosColumn, Scorer,
+    write_attachment_errors, write_bucket_scores, write_confusion_examples, write_cumulative_scores, write_distance_direction_errors, write_distance_error_histogram, write_label_frequency_scores, write_label_scores, write_las_strata, write_sentence_scores,
+};
+
+mod enhanced;
+pub use crate::enhanced::{evaluate_enhanced, ElasCounts, EnhancedArc, EnhancedGraph, EnhancedReader};
+
+mod feats;
+pub use crate::feats::{parse_features, FeatsCounts, FeatureCounts};
+
+mod files;
+pub use crate::files::{is_file_pattern, pair_files};
+
+mod genre;
+pub use crate::genre::{read_genres, UNKNOWN_GENRE};
+
+mod metrics;
+pub use crate::metrics::{f_beta, ratio, AttachmentCounts, RelPrf, RootPrf, Rounding, SkipCounts};
+
+pub mod rng;
+
+mod references;
+pub use crate::references::evaluate_best_reference;
+
+mod rel_rules;
+pub use crate::rel_rules::{strip_subtype, RelRules};
+
+mod root_head;
+pub use crate::root_head::{RootHead, RootHeadReader};
+
+mod segmentation;
+pub use crate::segmentation::{evaluate_segmentation, SegmentationCounts};
+
+mod summary;
+pub use crate::summary::{run_statistics, write_summary_table, MeanStd, RootSummary, Summary};
+
+mod weights;
+pub use crate::weights::TokenWeights;
+
+/// A boxed reader of any sentence format.
+pub struct BoxedReader<'a>(pub Box<dyn ReadSentence + 'a>);
+
+impl<'a> ReadSentence for BoxedReader<'a> {
+    fn read_sentence(&mut self) -> Result<Option<Sentence>, Error> {
+        self.0.read_sentence()
+    }
+}
+
+/// A reader over sentences that are already in memory.
+pub struct SentenceIter<I>(pub I);
+
+impl<I> ReadSentence for SentenceIter<I>
+where
+    I: Iterator<Item = Sentence>,
+{
+    fn read_sentence(&mut self) -> Result<Option<Sentence>, Error> {
+        Ok(self.0.next())
+    }
+}
+
+/// A reader that reads the sentences of several readers in sequence.
+pub struct ChainReader<'a> {
+    readers: VecDeque<BoxedReader<'a>>,
+}
+
+impl<'a> ChainReader<'a> {
+    pub fn new(readers: impl IntoIterator<Item = BoxedReader<'a>>) -> Self {
+        ChainReader {
+            readers: readers.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> ReadSentence for ChainReader<'a> {
+    fn read_sentence(&mut self) -> Result<Option<Sentence>, Error> {
+        while let Some(reader) = self.readers.front_mut() {
+            if let Some(sentence) = reader.read_sentence()? {
+                return Ok(Some(sentence));
+            }
+            self.readers.pop_front();
+        }
+        Ok(None)
+    }
+}
+
+pub trait GetFeature {
+    fn get_feature(&self, name: &str) -> Option<&str>;
+}
+
+impl GetFeature for Token {
+    fn get_feature(&self, name: &str) -> Option<&str> {
+        if let Some(features) = self.features() {
+            if let Some(feature) = features.as_map().get(name) {
+                return feature.as_ref().map(|f| f.as_str())
+            }
+        }
+        None
+    }
+}
@@ -1,90 +1,717 @@
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::collections::HashMap;
-use std::fmt::Display;
-use std::fmt;
-use std::hash::Hash;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use clap::{App, AppSettings, Arg, ArgMatches};
-use conllx::io::{Reader, ReadSentence};
-use conllx::token::Token;
-use failure::{Error};
-use itertools::Itertools;
-use stdinout::OrExit;
+#[cfg(feature = "serde")]
+use clap::SubCommand;
+use conllx::io::Reader;
+use dep_eval::{bootstrap, browse, conllu, is_file_pattern, pair_files, paired_bootstrap, ratio, read_error_sentences, read_genres, run_statistics, write_attachment_errors, write_bucket_scores, write_confusion_examples, write_cumulative_scores, write_distance_direction_errors, write_distance_error_histogram, write_label_frequency_scores, write_label_scores, write_las_strata, write_sentence_scores, write_summary_table, align_by_sent_id, BoxedReader, Buckets, ChainReader, ClauseRelations, count_sentences, evaluate_best_reference, evaluate_enhanced, evaluate_segmentation, evaluate_with, ElasCounts, EnhancedReader, EvalOptions, EvalWriters, PosColumn, RelRules, RootHead, RootHeadReader, Rounding, SentenceIter, TokenWeights};
+use failure::{format_err, Error};
+use flate2::bufread::MultiGzDecoder;
+use stdinout::{Input, OrExit};
+
+#[cfg(feature = "serde")]
+use dep_eval::Confusion;
 
 pub fn main() -> Result<(), Error> {
     let matches = parse_args();
+    #[cfg(feature = "serde")]
+    if let Some(merge_matches) = matches.subcommand_matches(MERGE) {
+        return merge_confusions(merge_matches);
+    }
+    #[cfg(feature = "serde")]
+    if let Some(confusion_matches) = matches.subcommand_matches(CONFUSION) {
+        return process_confusion(confusion_matches);
+    }
     let val_path = matches
         .value_of(VALIDATION)
         .or_exit("Missing input path", 1);
-    let val_file = File::open(val_path).or_exit("Can't open validation file.", 1);
-    let mut val_reader = Reader::new(BufReader::new(val_file));
+    let identity_check = matches.is_present(IDENTITY_CHECK);
+    let agreement = matches.is_present(AGREEMENT);
+    let pred_path = if identity_check {
+        val_path
+    } else {
+        matches
+            .value_of(PREDICTION)
+            .or_exit("Missing input path", 1)
+    };
 
-    let pred_path = matches
-        .value_of(PREDICTION)
-        .or_exit("Missing input path", 1);
-    let pred_file = File::open(pred_path)?;
-    let mut pred_reader = Reader::new(BufReader::new(pred_file));
-
-    let mut deprel_confusion = Confusion::<String>::new("Deprels");
-    let mut distance_confusion = Confusion::<usize>::new("Dists");
-
-    let skip_punct = matches.is_present(SKIP_PUNCTUATION);
-
-    let mut correct_head = 0;
-    let mut correct_head_label = 0;
-    let mut total = 0;
-
-    while let (Ok(Some(val_sentence)), Ok(Some(pred_sentence))) = (val_reader.read_sentence(), pred_reader.read_sentence()) {
-        assert_eq!(val_sentence.len(), pred_sentence.len());
-        for (idx, (val_token, pred_token)) in val_sentence
-            .iter()
-            .filter_map(|t| t.token())
-            .zip(pred_sentence.iter().filter_map(|t| t.token()))
-            .enumerate() {
-            assert_eq!(val_token.form(), pred_token.form());
-            if skip_punct {
-                if val_token.pos().expect("Validation token missing POS").starts_with("PUNCT") {
-                    continue
-                }
+    if val_path == STDIN && pred_path == STDIN {
+        eprintln!("Only one of VALIDATION and PREDICTION can be read from stdin.");
+        std::process::exit(1)
+    }
+    let val_input = input(val_path);
+    let pred_input = input(pred_path);
+
+    let collapse_enhanced = matches.is_present(COLLAPSE_ENHANCED);
+    let conllu = matches.is_present(CONLLU) || collapse_enhanced;
+    let format = InputFormat {
+        conllu,
+        collapse_enhanced,
+        check_ids: !matches.is_present(NO_ID_CHECK),
+        root_head: matches.value_of(ROOT_HEAD).unwrap().parse::<RootHead>()?,
+        gzip: matches.is_present(GZIP),
+    };
+    if let RootHead::Value(value) = format.root_head {
+        if value < 0 && !conllu {
+            eprintln!("A negative --root-head requires --conllu, CoNLL-X heads cannot be negative.");
+            std::process::exit(1)
+        }
+    }
+
+    let checkpoints = matches.is_present(CHECKPOINTS);
+    let directories = match (is_file_set(val_path), is_file_set(pred_path)) {
+        (false, true) if checkpoints && Path::new(pred_path).is_dir() => false,
+        (true, _) if checkpoints => {
+            eprintln!("VALIDATION must be a file with --checkpoints.");
+            std::process::exit(1)
+        }
+        (true, true) => true,
+        (false, false) if !checkpoints => false,
+        (false, _) if checkpoints => {
+            eprintln!("PREDICTION must be a directory of predictions with --checkpoints.");
+            std::process::exit(1)
+        }
+        _ => {
+            eprintln!("VALIDATION and PREDICTION must both be files or both be directories or patterns.");
+            std::process::exit(1)
+        }
+    };
+
+    if matches.is_present(PRECOUNT) {
+        if val_path == STDIN || pred_path == STDIN {
+            eprintln!("Cannot count sentences in advance when reading from stdin.");
+            std::process::exit(1)
+        }
+        let (val_sents, val_tokens, pred_sents, pred_tokens) = if directories {
+            let (val_reader, pred_reader) = open_dir_readers(val_path, pred_path, format)?;
+            let (val_sents, val_tokens) = count_sentences(val_reader)?;
+            let (pred_sents, pred_tokens) = count_sentences(pred_reader)?;
+            (val_sents, val_tokens, pred_sents, pred_tokens)
+        } else {
+            let (val_sents, val_tokens) = count_sentences(open_reader(&val_input, format.validation())?)?;
+            let (pred_sents, pred_tokens) = count_sentences(open_reader(&pred_input, format)?)?;
+            (val_sents, val_tokens, pred_sents, pred_tokens)
+        };
+        if (val_sents, val_tokens) != (pred_sents, pred_tokens) {
+            eprintln!(
+                "Validation and prediction differ: {} sentences / {} tokens vs. {} sentences / {} tokens",
+                val_sents, val_tokens, pred_sents, pred_tokens
+            );
+            std::process::exit(1)
+        }
+    }
+
+    let rounding = matches
+        .value_of(ROUNDING)
+        .map(|r| r.parse::<Rounding>())
+        .transpose()?
+        .unwrap_or_default();
+    let precision = matches
+        .value_of(PRECISION)
+        .map(|n| n.parse::<usize>().or_exit("Cannot parse precision", 1))
+        .unwrap();
+    let bootstrap_samples = bootstrap_samples(&matches);
+
+    if matches.is_present(ENHANCED) {
+        let counts = if directories {
+            let mut counts = ElasCounts::default();
+            for (val_path, pred_path) in pair_files(val_path, pred_path)? {
+                counts += evaluate_enhanced(
+                    &mut EnhancedReader::new(open_file(&val_path, format.gzip)?),
+                    &mut EnhancedReader::new(open_file(&pred_path, format.gzip)?),
+                )
+                .or_exit("Evaluation failed", 1);
             }
-            let idx = idx+1 ;
-            let val_triple = val_sentence.dep_graph().head(idx).unwrap();
-            let val_head = val_triple.head();
-            let val_dist = i64::abs(val_head as i64 - idx as i64) as usize;
-            let val_rel = val_triple.relation().unwrap();
-            let pred_triple = pred_sentence.dep_graph().head(idx).unwrap();;
-            let pred_head = pred_triple.head();
-            let pred_dist = i64::abs(pred_head as i64 - idx as i64) as usize;
-            let pred_rel = pred_triple.relation().unwrap();
-            distance_confusion.insert(val_dist, pred_dist);
+            counts
+        } else {
+            evaluate_enhanced(
+                &mut EnhancedReader::new(open_buf_read(&val_input, format.gzip)?),
+                &mut EnhancedReader::new(open_buf_read(&pred_input, format.gzip)?),
+            )
+            .or_exit("Evaluation failed", 1)
+        };
+        println!("ELAS P: {}", rounding.format(counts.precision(), precision));
+        println!("ELAS R: {}", rounding.format(counts.recall(), precision));
+        println!("ELAS F1: {}", rounding.format(counts.f1(), precision));
+        println!("EUAS P: {}", rounding.format(counts.unlabeled_precision(), precision));
+        println!("EUAS R: {}", rounding.format(counts.unlabeled_recall(), precision));
+        println!("EUAS F1: {}", rounding.format(counts.unlabeled_f1(), precision));
+        println!("Validation arcs: {}", counts.gold);
+        println!("Prediction arcs: {}", counts.predicted);
+        println!("Sentences: {}", counts.sentences);
+        return Ok(());
+    }
+
+    if checkpoints {
+        if val_path == STDIN {
+            eprintln!("Cannot select a checkpoint when reading VALIDATION from stdin.");
+            std::process::exit(1)
+        }
+        let metric = matches.value_of(SELECT_METRIC).unwrap();
+        let options = eval_options(&matches, rounding, precision)?;
+        let mut pred_paths = fs::read_dir(pred_path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        pred_paths.retain(|path| path.is_file());
+        pred_paths.sort();
+        let mut summaries = Vec::new();
+        for path in &pred_paths {
+            let val_reader = format.validation().reader(open_file(Path::new(val_path), format.gzip)?);
+            let pred_reader = format.reader(open_file(path, format.gzip)?);
+            let result = evaluate_with(val_reader, pred_reader, &options, &mut EvalWriters::default())
+                .or_exit(format!("Evaluation of {} failed", path.display()), 1);
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            summaries.push((name, result.summary()));
+        }
+        write_summary_table(io::stdout().lock(), &summaries, rounding, precision)?;
+
+        // Distance errors are better when lower, all other metrics when
+        // higher. Ties go to the first prediction in file name order.
+        let lower_is_better = metric.ends_with("_error");
+        let mut best: Option<(&str, f32)> = None;
+        for (name, summary) in &summaries {
+            let score = summary
+                .scores()
+                .into_iter()
+                .find(|&(score_name, _)| score_name == metric)
+                .map(|(_, score)| score)
+                .unwrap();
+            let better = match best {
+                Some((_, best_score)) if lower_is_better => score < best_score,
+                Some((_, best_score)) => score > best_score,
+                None => true,
+            };
+            if better {
+                best = Some((name, score));
+            }
+        }
+        if let Some((name, score)) = best {
+            println!("Best {}: {} ({})", metric, name, rounding.format(score, precision));
+        }
+        return Ok(());
+    }
 
-            deprel_confusion.insert(val_rel, pred_rel);
+    if matches.is_present(BROWSE) {
+        if val_path == STDIN || pred_path == STDIN {
+            eprintln!("Browsing errors reads commands from stdin, VALIDATION and PREDICTION must be files.");
+            std::process::exit(1)
+        }
+        let (val_reader, pred_reader) = if directories {
+            open_dir_readers(val_path, pred_path, format).or_exit("Can't open directories", 1)
+        } else {
+            (open_reader(&val_input, format.validation())?, open_reader(&pred_input, format)?)
+        };
+        let options = eval_options(&matches, rounding, precision)?;
+        let sentences = read_error_sentences(val_reader, pred_reader, &options).or_exit("Cannot read sentences", 1);
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        return browse(&sentences, stdin.lock(), stdout.lock());
+    }
+
+    if matches.is_present(SEGMENTATION) {
+        let (val_reader, pred_reader) = if directories {
+            open_dir_readers(val_path, pred_path, format).or_exit("Can't open directories", 1)
+        } else {
+            (open_reader(&val_input, format.validation())?, open_reader(&pred_input, format)?)
+        };
+        let counts = evaluate_segmentation(val_reader, pred_reader).or_exit("Evaluation failed", 1);
+        println!("Sentence P: {}", rounding.format(counts.sentence_precision(), precision));
+        println!("Sentence R: {}", rounding.format(counts.sentence_recall(), precision));
+        println!("Sentence F1: {}", rounding.format(counts.sentence_f1(), precision));
+        println!("Word P: {}", rounding.format(counts.word_precision(), precision));
+        println!("Word R: {}", rounding.format(counts.word_recall(), precision));
+        println!("Word F1: {}", rounding.format(counts.word_f1(), precision));
+        println!("UAS P: {}", rounding.format(counts.uas_precision(), precision));
+        println!("UAS R: {}", rounding.format(counts.uas_recall(), precision));
+        println!("UAS F1: {}", rounding.format(counts.uas_f1(), precision));
+        println!("LAS P: {}", rounding.format(counts.las_precision(), precision));
+        println!("LAS R: {}", rounding.format(counts.las_recall(), precision));
+        println!("LAS F1: {}", rounding.format(counts.las_f1(), precision));
+        println!("Validation sentences: {}", counts.gold_sentences);
+        println!("Prediction sentences: {}", counts.predicted_sentences);
+        println!("Validation words: {}", counts.gold_words);
+        println!("Prediction words: {}", counts.predicted_words);
+        return Ok(());
+    }
 
-            correct_head += (pred_head == val_head) as usize;
-            correct_head_label += (pred_triple == val_triple) as usize;
-            total += 1;
+    // Check the sentence range before any output is written. Ranges of
+    // stdin input are checked during evaluation.
+    let sentence_range = sentence_range(&matches);
+    if let (Some((start, end)), false) = (sentence_range, val_path == STDIN) {
+        let (n_sentences, _) = if directories {
+            count_sentences(open_dir_readers(val_path, pred_path, format)?.0)?
+        } else {
+            count_sentences(open_reader(&val_input, format.validation())?)?
+        };
+        if end > n_sentences {
+            eprintln!(
+                "Sentence range {}:{} exceeds the {} sentences of the input.",
+                start, end, n_sentences
+            );
+            std::process::exit(1)
         }
     }
 
-    if let Ok(Some(_)) = val_reader.read_sentence() {
-        eprintln!("Val reader not exhausted.");
+    let align_sent_ids = matches.is_present(ALIGN_BY_SENT_ID);
+    if align_sent_ids && (directories || !conllu) {
+        eprintln!("Aligning by sent_id requires CoNLL-U files.");
         std::process::exit(1)
     }
 
-    if let Ok(Some(_)) = pred_reader.read_sentence() {
-        eprintln!("Pred reader not exhausted.");
+    let genres = match matches.value_of(GENRE_REPORT) {
+        Some(_) if val_path == STDIN => {
+            eprintln!("Cannot read genres when reading VALIDATION from stdin.");
+            std::process::exit(1)
+        }
+        Some(_) => {
+            let key = matches.value_of(GENRE_KEY).unwrap();
+            let mut genres = Vec::new();
+            for path in validation_paths(val_path, pred_path, directories)? {
+                let mut reader = format.validation().conllu_reader(open_file(&path, format.gzip)?);
+                genres.extend(read_genres(&mut reader, key)?);
+            }
+            Some(genres)
+        }
+        None => None,
+    };
+
+    let mut unmatched = None;
+    let mut sent_ids = None;
+    let (val_reader, pred_reader) = if align_sent_ids {
+        let alignment = align_by_sent_id(
+            &mut format.validation().conllu_reader(open_buf_read(&val_input, format.gzip)?),
+            &mut format.conllu_reader(open_buf_read(&pred_input, format.gzip)?),
+        )
+        .or_exit("Cannot align sentences", 1);
+        for id in &alignment.unmatched_val {
+            eprintln!("No prediction for sent_id: {}", id);
+        }
+        for id in &alignment.unmatched_pred {
+            eprintln!("No validation sentence for sent_id: {}", id);
+        }
+        unmatched = Some((alignment.unmatched_val.len(), alignment.unmatched_pred.len()));
+        sent_ids = Some(alignment.ids);
+        (
+            BoxedReader(Box::new(SentenceIter(alignment.val.into_iter()))),
+            BoxedReader(Box::new(SentenceIter(alignment.pred.into_iter()))),
+        )
+    } else if directories {
+        open_dir_readers(val_path, pred_path, format).or_exit("Can't open directories", 1)
+    } else {
+        (
+            open_reader(&val_input, format.validation()).or_exit("Can't open validation file.", 1),
+            open_reader(&pred_input, format)?,
+        )
+    };
+
+    // Identify sentences in the per-sentence scores by sent_id if every
+    // validation sentence has one.
+    let list_sentences = matches.is_present(PER_SENTENCE) || matches.is_present(LAS_STRATA_SENTENCES);
+    if sent_ids.is_none() && conllu && val_path != STDIN && list_sentences {
+        let mut ids = Some(Vec::new());
+        for path in validation_paths(val_path, pred_path, directories)? {
+            let file_ids = conllu::read_sent_ids(&mut format.validation().conllu_reader(open_file(&path, format.gzip)?))?;
+            ids = ids.zip(file_ids).map(|(mut ids, file_ids)| {
+                ids.extend(file_ids);
+                ids
+            });
+        }
+        sent_ids = ids;
+    }
+
+    let options = eval_options(&matches, rounding, precision)?;
+
+    let mut writers = EvalWriters {
+        errors: match matches.value_of(ERRORS) {
+            Some(file_name) => Some(Box::new(BufWriter::new(File::create(file_name)?))),
+            None => None,
+        },
+        error_sentences: match matches.value_of(ERROR_SENTENCES) {
+            Some(file_name) => Some(Box::new(BufWriter::new(File::create(file_name)?))),
+            None => None,
+        },
+        merged: match matches.value_of(MERGED_OUT) {
+            Some(file_name) => Some(Box::new(BufWriter::new(File::create(file_name)?))),
+            None => None,
+        },
+    };
+
+    let result = evaluate_with(val_reader, pred_reader, &options, &mut writers)
+        .or_exit("Evaluation failed", 1);
+    if let Some(writer) = &mut writers.errors {
+        writer.flush()?;
+    }
+    if let Some(writer) = &mut writers.error_sentences {
+        writer.flush()?;
+    }
+    if let Some(writer) = &mut writers.merged {
+        writer.flush()?;
+    }
+
+    if identity_check && (result.correct_head != result.total || result.correct_head_label != result.total) {
+        eprintln!(
+            "Identity check failed: {} of {} heads and {} of {} labeled heads match",
+            result.correct_head, result.total, result.correct_head_label, result.total
+        );
         std::process::exit(1)
     }
 
-    println!("UAS: {:.4}", correct_head as f32 / total as f32);
-    println!("LAS: {:.4}", correct_head_label as f32 / total as f32);
+    let root_prf = &result.root_prf;
+    let deprel_confusion = &result.deprel_confusion;
+    let distance_confusion = &result.distance_confusion;
+    let direction_confusion = &result.direction_confusion;
+    let confusion_sep = matches.value_of(CONFUSION_SEP).unwrap_or("\t");
+    let confusion_header = matches.is_present(CONFUSION_HEADER);
+
+    // Computed once, so that standard output and the summary files agree.
+    let summary = result.summary();
+
+    if options.sample_rate.is_some() {
+        println!(
+            "Sampled sentences: {} of {} (scores are estimates)",
+            result.sentences,
+            result.sentences + result.unsampled_sentences
+        );
+    }
+    if agreement {
+        println!("Head agreement: {}", rounding.format(summary.uas, precision));
+        println!("Head+label agreement: {}", rounding.format(summary.las, precision));
+        println!("Label agreement: {}", rounding.format(summary.la, precision));
+        println!("Deprel kappa: {}", rounding.format(deprel_confusion.kappa() as f32, precision));
+    } else {
+        println!("UAS: {}", rounding.format(summary.uas, precision));
+        println!("UUAS: {}", rounding.format(result.uuas(), precision));
+        println!("LAS: {}", rounding.format(summary.las, precision));
+        println!("UEM: {}", rounding.format(result.uem(), precision));
+        println!("LEM: {}", rounding.format(result.lem(), precision));
+        if options.weights.is_some() {
+            println!("Weighted UAS: {}", rounding.format(result.weighted_uas(), precision));
+            println!("Weighted LAS: {}", rounding.format(result.weighted_las(), precision));
+        }
+        println!("CLAS: {}", rounding.format(result.clas(), precision));
+        println!("LA: {}", rounding.format(summary.la, precision));
+        println!("Macro-LAS: {}", rounding.format(result.macro_las(), precision));
+        println!("Root P: {}", rounding.format(summary.root.precision, precision));
+        println!("Root R: {}", rounding.format(summary.root.recall, precision));
+        println!("Root F1: {}", rounding.format(summary.root.f1, precision));
+        for rel in &options.target_rels {
+            let prf = &result.target_rel_prf[rel];
+            println!("{} UP: {}", rel, rounding.format(prf.unlabeled_precision(), precision));
+            println!("{} UR: {}", rel, rounding.format(prf.unlabeled_recall(), precision));
+            println!("{} UF1: {}", rel, rounding.format(prf.unlabeled_f1(), precision));
+            println!("{} LP: {}", rel, rounding.format(prf.precision(), precision));
+            println!("{} LR: {}", rel, rounding.format(prf.recall(), precision));
+            println!("{} LF1: {}", rel, rounding.format(prf.f1(), precision));
+        }
+        println!("POS acc: {}", rounding.format(result.pos_accuracy(), precision));
+        println!("Lemma acc: {}", rounding.format(result.lemma_accuracy(), precision));
+        println!("Deprel kappa: {}", rounding.format(deprel_confusion.kappa() as f32, precision));
+        println!("Deprel MCC: {}", rounding.format(deprel_confusion.mcc() as f32, precision));
+        println!("Mean distance error: {}", rounding.format(summary.mean_distance_error, precision));
+        println!("Median distance error: {}", rounding.format(summary.median_distance_error, precision));
+        println!("Exact distance: {}", rounding.format(summary.exact_distance, precision));
+    }
+    if let Some(n_samples) = bootstrap_samples {
+        let seed = matches.value_of(SEED).unwrap().parse::<u64>().or_exit("Cannot parse seed", 1);
+        let ci = bootstrap(&result.sentence_scores, n_samples, seed);
+        println!(
+            "UAS 95% CI: [{}, {}]",
+            rounding.format(ci.uas.lower, precision),
+            rounding.format(ci.uas.upper, precision)
+        );
+        println!(
+            "LAS 95% CI: [{}, {}]",
+            rounding.format(ci.las.lower, precision),
+            rounding.format(ci.las.upper, precision)
+        );
+    }
+    if let Some(runs) = matches.values_of(RUN) {
+        let runs = runs.collect::<Vec<_>>();
+        let mut summaries = vec![summary.clone()];
+        for run in runs.chunks(2) {
+            let (val_reader, pred_reader) =
+                open_run_readers(run[0], run[1], format).or_exit(format!("Cannot open run {} {}", run[0], run[1]), 1);
+            let run_result = evaluate_with(val_reader, pred_reader, &options, &mut EvalWriters::default())
+                .or_exit(format!("Evaluation of run {} {} failed", run[0], run[1]), 1);
+            summaries.push(run_result.summary());
+        }
+        println!("Runs: {}", summaries.len());
+        for (name, stats) in run_statistics(&summaries) {
+            println!(
+                "{} mean: {} ± {}",
+                name,
+                rounding.format(stats.mean, precision),
+                rounding.format(stats.std, precision)
+            );
+        }
+    }
+    if let Some(references) = matches.values_of(REFERENCE) {
+        if val_path == STDIN || pred_path == STDIN || directories {
+            eprintln!("Scoring against several references requires VALIDATION and PREDICTION files.");
+            std::process::exit(1)
+        }
+        let references = references.collect::<Vec<_>>();
+        println!("Reference 1 UAS: {}", rounding.format(summary.uas, precision));
+        println!("Reference 1 LAS: {}", rounding.format(summary.las, precision));
+        for (idx, path) in references.iter().enumerate() {
+            let ref_input = input(path);
+            let ref_result = evaluate_with(
+                open_reader(&ref_input, format.validation())?,
+                open_reader(&pred_input, format)?,
+                &options,
+                &mut EvalWriters::default(),
+            )
+            .or_exit(format!("Evaluation against reference {} failed", path), 1);
+            println!("Reference {} UAS: {}", idx + 2, rounding.format(ref_result.uas(), precision));
+            println!("Reference {} LAS: {}", idx + 2, rounding.format(ref_result.las(), precision));
+        }
+        let ref_inputs = references.iter().map(|path| input(path)).collect::<Vec<_>>();
+        let mut val_readers = vec![open_reader(&val_input, format.validation())?];
+        for ref_input in &ref_inputs {
+            val_readers.push(open_reader(ref_input, format.validation())?);
+        }
+        let best = evaluate_best_reference(val_readers, open_reader(&pred_input, format)?, &options)
+            .or_exit("Evaluation against the references failed", 1);
+        println!("Best-reference UAS: {}", rounding.format(best.uas(), precision));
+        println!("Best-reference LAS: {}", rounding.format(best.las(), precision));
+    }
+    if let Some(pred2_path) = matches.value_of(PREDICTION2) {
+        if val_path == STDIN {
+            eprintln!("Cannot compare two predictions when reading VALIDATION from stdin.");
+            std::process::exit(1)
+        }
+        let pred2_input = input(pred2_path);
+        let val_reader = open_reader(&val_input, format.validation())?;
+        let pred2_reader = open_reader(&pred2_input, format)?;
+        let result2 = evaluate_with(val_reader, pred2_reader, &options, &mut EvalWriters::default())
+            .or_exit("Evaluation of second prediction failed", 1);
+        let n_samples = bootstrap_samples.unwrap_or(1000);
+        let seed = matches.value_of(SEED).unwrap().parse::<u64>().or_exit("Cannot parse seed", 1);
+        let test = paired_bootstrap(&result.sentence_scores, &result2.sentence_scores, n_samples, seed);
+        println!("LAS 2: {}", rounding.format(result2.las(), precision));
+        println!("LAS diff (2 - 1): {}", rounding.format(test.las_delta, precision));
+        println!("Mean sentence LAS diff (2 - 1): {}", rounding.format(test.mean_sentence_delta, precision));
+        println!("p-value: {}", rounding.format(test.p_value, precision));
+    }
+    if matches.is_present(PROJECTIVITY) {
+        let projective = &result.projective_scores;
+        let non_projective = &result.non_projective_scores;
+        println!("Projective UAS: {}", rounding.format(projective.uas(), precision));
+        println!("Projective LAS: {}", rounding.format(projective.las(), precision));
+        println!("Non-projective UAS: {}", rounding.format(non_projective.uas(), precision));
+        println!("Non-projective LAS: {}", rounding.format(non_projective.las(), precision));
+        println!("Projective tokens: {}", projective.total);
+        println!("Non-projective tokens: {}", non_projective.total);
+        let projective = &result.projective_sentence_scores;
+        let non_projective = &result.non_projective_sentence_scores;
+        println!("Projective sentences UAS: {}", rounding.format(projective.uas(), precision));
+        println!("Projective sentences LAS: {}", rounding.format(projective.las(), precision));
+        println!("Non-projective sentences UAS: {}", rounding.format(non_projective.uas(), precision));
+        println!("Non-projective sentences LAS: {}", rounding.format(non_projective.las(), precision));
+        println!("Projective sentences tokens: {}", projective.total);
+        println!("Non-projective sentences tokens: {}", non_projective.total);
+    }
+    if let Some((unmatched_val, unmatched_pred)) = unmatched {
+        println!("Unmatched validation sentences: {}", unmatched_val);
+        println!("Unmatched prediction sentences: {}", unmatched_pred);
+    }
+    if options.ignore_root_rel {
+        println!("Ignored root relations: {}", result.ignored_root_rels);
+    }
+    if writers.errors.is_some() && options.max_errors_per_sentence.is_some() {
+        println!("Suppressed errors: {}", result.suppressed_errors);
+    }
+    if matches.is_present(SPURIOUS_RELS) {
+        println!("Spurious relations:");
+        for (rel, count) in deprel_confusion.prediction_only() {
+            println!("{}\t{}", rel, count);
+        }
+    }
+
+    if let Some(k) = matches.value_of(TOP_CONFUSIONS) {
+        let k = k.parse::<usize>().or_exit("Cannot parse number of confusions", 1);
+        println!("Top confusions:");
+        for (gold, pred, count) in deprel_confusion.top_confusions(k) {
+            println!("{}\t{}\t{}", gold, pred, count);
+        }
+    }
+    if options.skip_mismatched {
+        println!("Skipped sentences (length mismatch): {}", result.skipped.length_mismatch);
+        println!("Skipped sentences (form mismatch): {}", result.skipped.form_mismatch);
+        println!("Skipped tokens (missing annotation): {}", result.skipped.missing_annotation);
+    }
+    println!("Missing heads: {}", result.missing_heads);
+    println!("Sentences: {}", summary.sentences);
+    println!("Tokens: {}", summary.tokens);
+    if matches.is_present(STATISTICS) {
+        let non_projective = result.non_projective_scores.total;
+        println!("Statistics:");
+        println!("Mean sentence length: {}", rounding.format(result.mean_sentence_length(), precision));
+        println!(
+            "Non-projective arcs: {} ({})",
+            non_projective,
+            rounding.format(ratio(non_projective, result.total), precision)
+        );
+        println!("Gold relations:");
+        for (rel, score) in &result.rel_scores {
+            println!("{}\t{}\t{}", rel, score.total, rounding.format(ratio(score.total, result.total), precision));
+        }
+    }
 
+    if let Some(file_name) = matches.value_of(JSON) {
+        if file_name == "-" {
+            summary.write_json(io::stdout().lock(), rounding, precision)?;
+        } else {
+            let mut writer = BufWriter::new(File::create(file_name)?);
+            summary.write_json(&mut writer, rounding, precision)?;
+        }
+    }
+    if let Some(file_name) = matches.value_of(SUMMARY) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        summary.write_tsv(&mut writer, rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(PER_SENTENCE) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_sentence_scores(
+            &mut writer,
+            &result.sentence_scores,
+            &result.sentence_indices,
+            sent_ids.as_deref(),
+            rounding,
+            precision,
+        )?;
+    }
+    if let Some(file_name) = matches.value_of(LEARNING_CURVE) {
+        let n_sentences = result.sentences;
+        let mut ends = match matches.value_of(CURVE_FRACTIONS) {
+            Some(fractions) => fractions
+                .split(',')
+                .map(|f| {
+                    let f = f.trim().parse::<f64>().or_exit("Cannot parse curve fraction", 1);
+                    (f * n_sentences as f64).round() as usize
+                })
+                .collect::<Vec<_>>(),
+            None => {
+                let step = matches
+                    .value_of(CURVE_STEP)
+                    .unwrap()
+                    .parse::<usize>()
+                    .or_exit("Cannot parse curve step", 1)
+                    .max(1);
+                (1..=n_sentences / step).map(|n| n * step).collect()
+            }
+        };
+        ends.push(n_sentences);
+        ends.sort_unstable();
+        ends.dedup();
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_cumulative_scores(&mut writer, &result.cumulative_scores(&ends), rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(LAS_STRATA) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_las_strata(
+            &mut writer,
+            &result.las_strata(),
+            matches.is_present(LAS_STRATA_SENTENCES),
+            sent_ids.as_deref(),
+            rounding,
+            precision,
+        )?;
+    }
+    if let Some(file_name) = matches.value_of(LENGTH_BUCKETS) {
+        let buckets = Buckets::parse(matches.value_of(LENGTH_BOUNDS).unwrap())?;
+        let length_buckets = result.length_buckets(&buckets);
+        if file_name == "-" {
+            write_bucket_scores(io::stdout().lock(), &length_buckets, rounding, precision)?;
+        } else {
+            let mut writer = BufWriter::new(File::create(file_name)?);
+            write_bucket_scores(&mut writer, &length_buckets, rounding, precision)?;
+        }
+    }
+    if let Some(file_name) = matches.value_of(LABEL_FREQUENCY) {
+        let buckets = Buckets::parse(matches.value_of(LABEL_FREQUENCY_BOUNDS).unwrap())?;
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_label_frequency_scores(&mut writer, &result.label_frequency_buckets(&buckets), rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(DISTANCE_DIRECTION) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        let scores = result.distance_direction_buckets(options.distance_buckets.as_ref());
+        write_distance_direction_errors(&mut writer, &scores, rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(DISTANCE_ERROR_HIST) {
+        let max = matches
+            .value_of(DISTANCE_ERROR_MAX)
+            .unwrap()
+            .parse::<usize>()
+            .or_exit("Cannot parse maximum distance error", 1);
+        let histogram = result.distance_error_histogram(max);
+        if file_name == "-" {
+            write_distance_error_histogram(io::stdout().lock(), &histogram, rounding, precision)?;
+        } else {
+            let mut writer = BufWriter::new(File::create(file_name)?);
+            write_distance_error_histogram(&mut writer, &histogram, rounding, precision)?;
+        }
+    }
+    if let Some(file_name) = matches.value_of(ATTACHMENT_ERRORS) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_attachment_errors(&mut writer, &result.rel_scores, rounding, precision)?;
+    }
+    if let (Some(file_name), Some(genres)) = (matches.value_of(GENRE_REPORT), &genres) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_bucket_scores(&mut writer, &result.group_scores(genres), rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(FEATS_ACCURACY) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        result.feats.write(&mut writer, rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(POS_ACCURACIES) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write_label_scores(&mut writer, &result.pos_scores, rounding, precision)?;
+    }
+    if let Some(file_name) = matches.value_of(ROOT_PRF) {
+        let out = File::create(file_name).unwrap();
+        let mut writer = BufWriter::new(out);
+        root_prf.write(&mut writer, rounding, precision).unwrap();
+    }
     if let Some(file_name) = matches.value_of(DEPREL_CONFUSION) {
         let out = File::create(file_name).unwrap();
         let mut writer = BufWriter::new(out);
-        write!(writer, "{}", deprel_confusion).unwrap();
+        deprel_confusion.write(&mut writer, confusion_sep, confusion_header).unwrap();
+    }
+    #[cfg(feature = "serde")]
+    if let Some(file_name) = matches.value_of(DEPREL_CONFUSION_JSON) {
+        let writer = BufWriter::new(File::create(file_name)?);
+        serde_json::to_writer(writer, deprel_confusion)?;
+    }
+    if let Some(dir) = matches.value_of(CONFUSION_EXAMPLES) {
+        let k = matches
+            .value_of(CONFUSION_EXAMPLE_PAIRS)
+            .unwrap()
+            .parse::<usize>()
+            .or_exit("Cannot parse number of confusion example pairs", 1);
+        fs::create_dir_all(dir)?;
+        for (rank, (gold, pred, _)) in deprel_confusion.top_confusions(k).into_iter().enumerate() {
+            let file_name = format!("{:02}-{}-{}.tsv", rank + 1, gold, pred).replace('/', "_");
+            let mut writer = BufWriter::new(File::create(Path::new(dir).join(file_name))?);
+            write_confusion_examples(&mut writer, &result.confusion_examples[&(gold, pred)])?;
+        }
+    }
+    if let Some(file_name) = matches.value_of(DEPREL_CONFUSION_PRECISION) {
+        let out = File::create(file_name).unwrap();
+        let mut writer = BufWriter::new(out);
+        deprel_confusion.write_normalized_col(&mut writer).unwrap();
+    }
+    if let Some(file_name) = matches.value_of(DEPREL_PRF) {
+        let beta = matches
+            .value_of(FBETA)
+            .unwrap()
+            .parse::<f32>()
+            .or_exit("Cannot parse beta", 1);
+        let out = File::create(file_name).unwrap();
+        let mut writer = BufWriter::new(out);
+        deprel_confusion.write_prf_beta(&mut writer, beta).unwrap();
     }
     if let Some(file_name) = matches.value_of(DEPREL_ACCURACIES) {
         let out = File::create(file_name).unwrap();
@@ -95,45 +722,530 @@ pub fn main() -> Result<(), Error> {
     if let Some(file_name) = matches.value_of(DISTANCE_CONFUSION) {
         let out = File::create(file_name).unwrap();
         let mut writer = BufWriter::new(out);
-        write!(writer, "{}", distance_confusion).unwrap();
-//        write!(writer, "{}", deprel_confusion).unwrap();
+        distance_confusion.write(&mut writer, confusion_sep, confusion_header).unwrap();
     }
     if let Some(file_name) = matches.value_of(DISTANCE_ACCURACIES) {
         let out = File::create(file_name).unwrap();
         let mut writer = BufWriter::new(out);
         distance_confusion.write_accuracies(&mut writer).unwrap();
     }
+    if let Some(file_name) = matches.value_of(DISTANCE_PAIRS) {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        writeln!(writer, "gold\tpredicted\tcount")?;
+        for (gold, pred, count) in distance_confusion.entries() {
+            writeln!(writer, "{}\t{}\t{}", gold, pred, count)?;
+        }
+    }
+    if let Some(file_name) = matches.value_of(DIRECTION_CONFUSION) {
+        let out = File::create(file_name).unwrap();
+        let mut writer = BufWriter::new(out);
+        direction_confusion.write(&mut writer, confusion_sep, confusion_header).unwrap();
+    }
+
+    // Relations that only occur in the prediction usually point to a
+    // label mapping problem, so always warn about them.
+    let spurious_rels = deprel_confusion.prediction_only();
+    if !spurious_rels.is_empty() {
+        eprintln!(
+            "Warning: {} predicted relation(s) never occur in the validation data:",
+            spurious_rels.len()
+        );
+        for (rel, count) in spurious_rels {
+            eprintln!("  {}\t{}", rel, count);
+        }
+    }
+    Ok(())
+}
+
+/// Get the evaluation options from the command line arguments.
+fn eval_options(matches: &ArgMatches, rounding: Rounding, precision: usize) -> Result<EvalOptions, Error> {
+    Ok(EvalOptions {
+        skip_punctuation: matches.is_present(SKIP_PUNCTUATION),
+        no_punct: matches.is_present(NO_PUNCT),
+        no_punct_confusion: matches.is_present(NO_PUNCT_CONFUSION),
+        punct_tags: matches
+            .value_of(PUNCT_TAGS)
+            .map(|tags| tags.split(',').map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
+        ignore_case_forms: matches.is_present(IGNORE_CASE_FORMS),
+        no_form_check: matches.is_present(NO_FORM_CHECK),
+        skip_mismatched: matches.is_present(SKIP_MISMATCHED),
+        ignore_rels: matches
+            .value_of(IGNORE_RELS)
+            .map(|rels| rels.split(',').map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
+        rel_rules: match (matches.value_of(REL_RULES), matches.value_of(REL_MAP)) {
+            (Some(path), _) => Some(RelRules::from_file(path)?),
+            (None, Some(path)) => Some(RelRules::from_map_file(path)?),
+            (None, None) => None,
+        },
+        clause_relations: matches.value_of(CLAUSE_IDS).map(ClauseRelations::from_file).transpose()?,
+        coarse_rels: matches.is_present(COARSE_RELS),
+        ignore_root_rel: matches.is_present(IGNORE_ROOT_REL),
+        max_errors_per_sentence: matches
+            .value_of(TOP_N_ERRORS_PER_SENTENCE)
+            .map(|n| n.parse::<usize>().or_exit("Cannot parse number of errors per sentence", 1)),
+        rounding,
+        precision: Some(precision),
+        pos_column: matches.value_of(POS_COLUMN).unwrap().parse::<PosColumn>()?,
+        threads: matches
+            .value_of(THREADS)
+            .map(|n| n.parse::<usize>().or_exit("Cannot parse number of threads", 1))
+            .unwrap(),
+        sentence_range: sentence_range(matches),
+        progress: if matches.is_present(PROGRESS) {
+            matches
+                .value_of(PROGRESS_INTERVAL)
+                .map(|n| n.parse::<usize>().or_exit("Cannot parse progress interval", 1))
+        } else {
+            None
+        },
+        sample_rate: matches.value_of(SAMPLE_RATE).map(|rate| {
+            let rate = rate.parse::<f64>().or_exit("Cannot parse sample rate", 1);
+            if !(rate > 0. && rate <= 1.) {
+                eprintln!("The sample rate must be in (0, 1].");
+                std::process::exit(1)
+            }
+            rate
+        }),
+        sample_seed: matches.value_of(SEED).unwrap().parse::<u64>().or_exit("Cannot parse seed", 1),
+        skip: matches.value_of(SKIP).unwrap().parse::<usize>().or_exit("Cannot parse number of skipped sentences", 1),
+        max_sentences: matches
+            .value_of(MAX_SENTENCES)
+            .map(|n| n.parse::<usize>().or_exit("Cannot parse maximum number of sentences", 1)),
+        weights: match (matches.value_of(WEIGHTS), matches.value_of(WEIGHT_FEATURE)) {
+            (Some(path), _) => match matches.value_of(WEIGHT_BY).unwrap() {
+                "rel" => Some(TokenWeights::relations_from_file(path)?),
+                "pos" => Some(TokenWeights::tags_from_file(path)?),
+                _ => Some(TokenWeights::from_file(path)?),
+            },
+            (None, Some(name)) => Some(TokenWeights::Feature(name.to_owned())),
+            (None, None) => None,
+        },
+        distance_buckets: matches
+            .value_of(DISTANCE_BUCKETS)
+            .map(Buckets::parse)
+            .transpose()?,
+        target_rels: matches
+            .values_of(TARGET_REL)
+            .map(|rels| rels.map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
+        confusion_exclude_root: matches.is_present(CONFUSION_EXCLUDE_ROOT),
+        confusion_examples: if matches.is_present(CONFUSION_EXAMPLES) {
+            matches
+                .value_of(CONFUSION_EXAMPLE_COUNT)
+                .map(|n| n.parse::<usize>().or_exit("Cannot parse number of confusion examples", 1))
+        } else {
+            None
+        },
+        sort_labels: matches.is_present(SORT_LABELS),
+    })
+}
+
+/// Get the number of bootstrap samples of --bootstrap.
+fn bootstrap_samples(matches: &ArgMatches) -> Option<usize> {
+    matches.value_of(BOOTSTRAP).map(|n| {
+        let n = n.parse::<usize>().or_exit("Cannot parse number of bootstrap samples", 1);
+        if n == 0 {
+            eprintln!("The number of bootstrap samples must be positive.");
+            std::process::exit(1)
+        }
+        n
+    })
+}
+
+/// Get the sentence range of --sentence-range as (start, end).
+fn sentence_range(matches: &ArgMatches) -> Option<(usize, usize)> {
+    matches
+        .value_of(SENTENCE_RANGE)
+        .map(|range| parse_sentence_range(range).or_exit("Invalid sentence range", 1))
+}
+
+fn parse_sentence_range(range: &str) -> Result<(usize, usize), Error> {
+    let mut parts = range.splitn(2, ':');
+    let start = parts.next().unwrap().trim().parse::<usize>();
+    let end = parts.next().map(|end| end.trim().parse::<usize>());
+    match (start, end) {
+        (Ok(start), Some(Ok(end))) if start < end => Ok((start, end)),
+        (Ok(_), Some(Ok(_))) => Err(format_err!("the range {} is empty", range)),
+        _ => Err(format_err!("expected START:END, found {}", range)),
+    }
+}
+
+/// Get the input for a path, `-` is stdin.
+fn input(path: &str) -> Input {
+    if path == STDIN {
+        Input::from(None::<&str>)
+    } else {
+        Input::from(Some(path))
+    }
+}
+
+/// Merge serialized confusion matrices, e.g. of evaluation shards.
+///
+/// The merged matrix and its accuracies are printed to stdout.
+#[cfg(feature = "serde")]
+fn merge_confusions(matches: &ArgMatches) -> Result<(), Error> {
+    let mut paths = matches.values_of(MATRICES).unwrap();
+    let mut merged = read_confusion(paths.next().unwrap());
+    for path in paths {
+        merge_confusion(&mut merged, read_confusion(path));
+    }
+    if matches.is_present(SORT_LABELS) {
+        merged.sort_values();
+    }
+
+    if let Some(file_name) = matches.value_of(JSON_OUT) {
+        serde_json::to_writer(BufWriter::new(File::create(file_name)?), &merged)?;
+    }
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    merged.write(&mut writer, "\t", false)?;
+    writeln!(writer)?;
+    merged.write_accuracies(&mut writer)?;
+    Ok(())
+}
+
+/// Post-process a serialized confusion matrix.
+///
+/// The matrix is merged with further matrices, pruned and sorted, in
+/// that order, and then written to stdout in the requested format.
+#[cfg(feature = "serde")]
+fn process_confusion(matches: &ArgMatches) -> Result<(), Error> {
+    let mut confusion = read_confusion(matches.value_of(MATRIX).unwrap());
+    for path in matches.values_of(MERGE_WITH).into_iter().flatten() {
+        merge_confusion(&mut confusion, read_confusion(path));
+    }
+    if let Some(min_count) = matches.value_of(PRUNE) {
+        confusion.prune(min_count.parse::<usize>().or_exit("Cannot parse minimum count", 1));
+    }
+    if matches.is_present(SORT_LABELS) {
+        confusion.sort_values();
+    }
+    let rounding = matches
+        .value_of(ROUNDING)
+        .map(|r| r.parse::<Rounding>())
+        .transpose()?
+        .unwrap_or_default();
+    confusion.set_rounding(rounding);
+    confusion.set_precision(
+        matches
+            .value_of(PRECISION)
+            .map(|n| n.parse::<usize>().or_exit("Cannot parse precision", 1))
+            .unwrap(),
+    );
+
+    if let Some(file_name) = matches.value_of(JSON_OUT) {
+        serde_json::to_writer(BufWriter::new(File::create(file_name)?), &confusion)?;
+    }
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    match matches.value_of(CONFUSION_OUTPUT).unwrap() {
+        "accuracies" => confusion.write_accuracies(&mut writer)?,
+        "normalized" => confusion.write_normalized_col(&mut writer)?,
+        "prf" => confusion.write_prf(&mut writer)?,
+        "json" => {
+            serde_json::to_writer(&mut writer, &confusion)?;
+            writeln!(writer)?;
+        }
+        _ => confusion.write(
+            &mut writer,
+            matches.value_of(CONFUSION_SEP).unwrap_or("\t"),
+            matches.is_present(CONFUSION_HEADER),
+        )?,
+    }
     Ok(())
 }
 
+/// Read a confusion matrix written with --deprel-confusion-json.
+#[cfg(feature = "serde")]
+fn read_confusion(path: &str) -> Confusion<String> {
+    let file = File::open(path).or_exit(format!("Cannot open {}", path), 1);
+    serde_json::from_reader(BufReader::new(file)).or_exit(format!("Cannot read confusion matrix {}", path), 1)
+}
+
+/// Merge two confusion matrices, exiting if they have different names.
+#[cfg(feature = "serde")]
+fn merge_confusion(merged: &mut Confusion<String>, confusion: Confusion<String>) {
+    if merged.name() != confusion.name() {
+        eprintln!(
+            "Cannot merge confusion matrices '{}' and '{}'",
+            merged.name(),
+            confusion.name()
+        );
+        std::process::exit(1)
+    }
+    merged.merge(confusion);
+}
+
+/// Get the validation files, the paired validation files in directory
+/// mode.
+fn validation_paths(val_path: &str, pred_path: &str, directories: bool) -> Result<Vec<PathBuf>, Error> {
+    if directories {
+        Ok(pair_files(val_path, pred_path)?.into_iter().map(|(val, _)| val).collect())
+    } else {
+        Ok(vec![PathBuf::from(val_path)])
+    }
+}
+
+/// Format of the input files.
+#[derive(Clone, Copy)]
+struct InputFormat {
+    conllu: bool,
+    collapse_enhanced: bool,
+    check_ids: bool,
+    root_head: RootHead,
+    gzip: bool,
+}
+
+impl InputFormat {
+    /// The format of validation files, enhanced dependencies are only
+    /// collapsed in predictions.
+    fn validation(self) -> Self {
+        InputFormat {
+            collapse_enhanced: false,
+            ..self
+        }
+    }
+
+    fn conllu_reader<R: BufRead>(self, read: R) -> conllu::Reader<R> {
+        conllu::Reader::new(read)
+            .collapse_enhanced(self.collapse_enhanced)
+            .check_ids(self.check_ids)
+            .root_head(self.root_head)
+    }
+
+    fn reader<'a>(self, read: impl BufRead + 'a) -> BoxedReader<'a> {
+        if self.conllu {
+            BoxedReader(Box::new(self.conllu_reader(read)))
+        } else {
+            BoxedReader(Box::new(RootHeadReader::new(Reader::new(read), self.root_head)))
+        }
+    }
+}
+
+/// Open a CoNLL-X or CoNLL-U reader.
+fn open_reader(input: &Input, format: InputFormat) -> Result<BoxedReader<'_>, Error> {
+    Ok(format.reader(open_buf_read(input, format.gzip)?))
+}
+
+/// Open readers that read all paired files of two directories in
+/// sequence.
+fn open_dir_readers(
+    val_dir: &str,
+    pred_dir: &str,
+    format: InputFormat,
+) -> Result<(BoxedReader<'static>, BoxedReader<'static>), Error> {
+    let mut val_readers = Vec::new();
+    let mut pred_readers = Vec::new();
+    for (val_path, pred_path) in pair_files(val_dir, pred_dir)? {
+        val_readers.push(format.validation().reader(open_file(&val_path, format.gzip)?));
+        pred_readers.push(format.reader(open_file(&pred_path, format.gzip)?));
+    }
+    Ok((
+        BoxedReader(Box::new(ChainReader::new(val_readers))),
+        BoxedReader(Box::new(ChainReader::new(pred_readers))),
+    ))
+}
+
+/// Check whether a VALIDATION or PREDICTION path is a directory or a
+/// file name pattern, see `pair_files`.
+fn is_file_set(path: &str) -> bool {
+    Path::new(path).is_dir() || is_file_pattern(path)
+}
+
+/// Open the readers of a run, two files or two directories.
+fn open_run_readers(
+    val_path: &str,
+    pred_path: &str,
+    format: InputFormat,
+) -> Result<(BoxedReader<'static>, BoxedReader<'static>), Error> {
+    match (is_file_set(val_path), is_file_set(pred_path)) {
+        (true, true) => open_dir_readers(val_path, pred_path, format),
+        (false, false) => Ok((
+            format.validation().reader(open_file(Path::new(val_path), format.gzip)?),
+            format.reader(open_file(Path::new(pred_path), format.gzip)?),
+        )),
+        _ => Err(format_err!(
+            "{} and {} must both be files or both be directories or patterns",
+            val_path,
+            pred_path
+        )),
+    }
+}
+
+/// Open an input, decompressing it if `gzip` is set or the input is a
+/// file with the `.gz` extension.
+fn open_buf_read(input: &Input, gzip: bool) -> Result<Box<dyn BufRead + '_>, Error> {
+    match input {
+        Input::File(path) => open_file(path, gzip),
+        Input::Stdin(_) if gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(input.buf_read()?)))),
+        Input::Stdin(_) => Ok(Box::new(input.buf_read()?)),
+    }
+}
+
+/// Open a file, decompressing it if `gzip` is set or the file has the
+/// `.gz` extension.
+fn open_file(path: &Path, gzip: bool) -> Result<Box<dyn BufRead>, Error> {
+    let file = File::open(path)?;
+    if gzip || path.extension().map(|ext| ext == "gz").unwrap_or(false) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file)))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
 static DEFAULT_CLAP_SETTINGS: &[AppSettings] = &[
     AppSettings::DontCollapseArgsInUsage,
     AppSettings::UnifiedHelpMessage,
 ];
 
+static STDIN: &str = "-";
+
 // Argument constants
 static VALIDATION: &str = "VALIDATION";
 static PREDICTION: &str = "PREDICTION";
 static DEPREL_CONFUSION: &str = "deprel_confusion";
 static DEPREL_ACCURACIES: &str = "deprel_accuracies";
+#[cfg(feature = "serde")]
+static DEPREL_CONFUSION_JSON: &str = "deprel-confusion-json";
+static DEPREL_CONFUSION_PRECISION: &str = "deprel-confusion-precision";
+static DEPREL_PRF: &str = "deprel-prf";
+static FBETA: &str = "fbeta";
 static DISTANCE_ACCURACIES: &str = "distance_confusion";
 static DISTANCE_CONFUSION: &str = "distance_accuracies";
 static SKIP_PUNCTUATION: &str = "skip_punctuation";
+static REL_RULES: &str = "rel-rules";
+static PRECOUNT: &str = "precount";
+static COLLAPSE_ENHANCED: &str = "collapse-enhanced";
+static CONLLU: &str = "conllu";
+static ERRORS: &str = "errors";
+static ERROR_SENTENCES: &str = "error-sentences";
+static TOP_N_ERRORS_PER_SENTENCE: &str = "top-n-errors-per-sentence";
+static SPURIOUS_RELS: &str = "spurious-rels";
+static ROUNDING: &str = "rounding";
+static IDENTITY_CHECK: &str = "identity-check";
+static MERGED_OUT: &str = "merged-out";
+static IGNORE_ROOT_REL: &str = "ignore-root-rel";
+static NO_PUNCT: &str = "no-punct";
+static NO_PUNCT_CONFUSION: &str = "no-punct-confusion";
+static PUNCT_TAGS: &str = "punct-tags";
+static IGNORE_RELS: &str = "ignore-rels";
+static PROJECTIVITY: &str = "projectivity";
+static THREADS: &str = "threads";
+static SUMMARY: &str = "summary";
+static TOP_CONFUSIONS: &str = "top-confusions";
+static IGNORE_CASE_FORMS: &str = "ignore-case-forms";
+static NO_FORM_CHECK: &str = "no-form-check";
+static ALIGN_BY_SENT_ID: &str = "align-by-sent-id";
+static ATTACHMENT_ERRORS: &str = "attachment-errors";
+static DISTANCE_DIRECTION: &str = "distance-direction";
+static PRECISION: &str = "precision";
+static GZIP: &str = "gzip";
+static POS_COLUMN: &str = "pos-column";
+static FEATS_ACCURACY: &str = "feats-accuracy";
+static ENHANCED: &str = "enhanced";
+static PROGRESS: &str = "progress";
+#[cfg(feature = "serde")]
+static MERGE: &str = "merge";
+#[cfg(feature = "serde")]
+static MATRICES: &str = "MATRICES";
+#[cfg(feature = "serde")]
+static JSON_OUT: &str = "json";
+#[cfg(feature = "serde")]
+static CONFUSION: &str = "confusion";
+#[cfg(feature = "serde")]
+static MATRIX: &str = "MATRIX";
+#[cfg(feature = "serde")]
+static MERGE_WITH: &str = "merge";
+#[cfg(feature = "serde")]
+static PRUNE: &str = "prune";
+#[cfg(feature = "serde")]
+static CONFUSION_OUTPUT: &str = "output";
+static SAMPLE_RATE: &str = "sample-rate";
+static PROGRESS_INTERVAL: &str = "progress-interval";
+static WEIGHTS: &str = "weights";
+static WEIGHT_FEATURE: &str = "weight-feature";
+static CONFUSION_SEP: &str = "confusion-sep";
+static CONFUSION_HEADER: &str = "confusion-header";
+static GENRE_KEY: &str = "genre-key";
+static GENRE_REPORT: &str = "genre-report";
+static ROOT_PRF: &str = "root-prf";
+static JSON: &str = "json";
+static DISTANCE_BUCKETS: &str = "distance-buckets";
+static DIRECTION_CONFUSION: &str = "direction-confusion";
+static COARSE_RELS: &str = "coarse-rels";
+static BOOTSTRAP: &str = "bootstrap";
+static SEED: &str = "seed";
+static PREDICTION2: &str = "prediction2";
+static PER_SENTENCE: &str = "per-sentence";
+static LENGTH_BUCKETS: &str = "length-buckets";
+static LENGTH_BOUNDS: &str = "length-bounds";
+static POS_ACCURACIES: &str = "pos-accuracies";
+static SENTENCE_RANGE: &str = "sentence-range";
+static TARGET_REL: &str = "target-rel";
+static CONFUSION_EXCLUDE_ROOT: &str = "confusion-exclude-root";
+static DISTANCE_PAIRS: &str = "distance-pairs";
+static NO_ID_CHECK: &str = "no-id-check";
+static ROOT_HEAD: &str = "root-head";
+static AGREEMENT: &str = "agreement";
+static REL_MAP: &str = "rel-map";
+static CLAUSE_IDS: &str = "clause_ids";
+static DISTANCE_ERROR_HIST: &str = "distance-error-hist";
+static DISTANCE_ERROR_MAX: &str = "distance-error-max";
+static CONFUSION_EXAMPLES: &str = "confusion-examples";
+static CONFUSION_EXAMPLE_PAIRS: &str = "confusion-example-pairs";
+static CONFUSION_EXAMPLE_COUNT: &str = "confusion-example-count";
+static LAS_STRATA: &str = "las-strata";
+static LAS_STRATA_SENTENCES: &str = "las-strata-sentences";
+static WEIGHT_BY: &str = "weight-by";
+static LEARNING_CURVE: &str = "learning-curve";
+static CURVE_STEP: &str = "curve-step";
+static CURVE_FRACTIONS: &str = "curve-fractions";
+static LABEL_FREQUENCY: &str = "label-frequency";
+static LABEL_FREQUENCY_BOUNDS: &str = "label-frequency-bounds";
+static SEGMENTATION: &str = "segmentation";
+static RUN: &str = "run";
+static SKIP_MISMATCHED: &str = "skip-mismatched";
+static STATISTICS: &str = "statistics";
+static SORT_LABELS: &str = "sort-labels";
+static REFERENCE: &str = "reference";
+static SKIP: &str = "skip";
+static MAX_SENTENCES: &str = "max-sentences";
+static BROWSE: &str = "browse";
+static CHECKPOINTS: &str = "checkpoints";
+static SELECT_METRIC: &str = "select-metric";
 
 fn parse_args() -> ArgMatches<'static> {
-    App::new("reduce-ptb")
+    let app = App::new("reduce-ptb")
         .settings(DEFAULT_CLAP_SETTINGS)
         .arg(
             Arg::with_name(VALIDATION)
-                .help("VALIDATION file")
+                .help("VALIDATION file, directory or quoted file name pattern such as 'gold/*.conllu' \
+                       ('-' for stdin)")
                 .index(1)
                 .required(true),
         )
         .arg(
             Arg::with_name(PREDICTION)
                 .index(2)
-                .help("PREDICTION")
-                .required(true),
+                .help("PREDICTION file, directory or quoted file name pattern such as 'pred/*.conllu' \
+                       ('-' for stdin)")
+                .required_unless(IDENTITY_CHECK),
+        )
+        .arg(
+            Arg::with_name(IDENTITY_CHECK)
+                .long(IDENTITY_CHECK)
+                .conflicts_with(PREDICTION)
+                .help("Score VALIDATION against itself and fail unless UAS and LAS are 1.0.")
+        )
+        .arg(
+            Arg::with_name(AGREEMENT)
+                .long(AGREEMENT)
+                .conflicts_with_all(&[IDENTITY_CHECK, ENHANCED, PREDICTION2])
+                .help("Compare two predictions without gold data: VALIDATION is the reference \
+                       prediction. Reports head, head+label and label agreement and the deprel \
+                       kappa instead of the accuracies.")
         )
         .arg(
             Arg::with_name(DEPREL_CONFUSION)
@@ -141,6 +1253,314 @@ fn parse_args() -> ArgMatches<'static> {
                 .long(DEPREL_CONFUSION)
                 .help("print deprel confusion matrix to file")
         )
+        .arg(
+            Arg::with_name(DEPREL_CONFUSION_PRECISION)
+                .takes_value(true)
+                .long(DEPREL_CONFUSION_PRECISION)
+                .value_name("FILE")
+                .help("print column-normalized (precision) deprel confusion matrix to file")
+        )
+        .arg(
+            Arg::with_name(DEPREL_PRF)
+                .takes_value(true)
+                .long(DEPREL_PRF)
+                .value_name("FILE")
+                .help("print deprel precision, recall and F-beta to file")
+        )
+        .arg(
+            Arg::with_name(FBETA)
+                .takes_value(true)
+                .long(FBETA)
+                .value_name("B")
+                .default_value("1.0")
+                .help("beta of the F-beta score: (1 + B^2) * P * R / (B^2 * P + R), \
+                       B = 1 is F1")
+        )
+        .arg(
+            Arg::with_name(SUMMARY)
+                .takes_value(true)
+                .long(SUMMARY)
+                .value_name("FILE")
+                .help("write UAS, LAS, LA, token and sentence counts and root scores as TSV to file")
+        )
+        .arg(
+            Arg::with_name(JSON)
+                .takes_value(true)
+                .long(JSON)
+                .value_name("FILE")
+                .help("write the scalar metrics as JSON object to file ('-' for stdout): \
+                       uas, las, la, uuas, clas, macro_las, uem, lem, pos_accuracy, lemma_accuracy, \
+                       tokens, sentences, mean_distance_error, median_distance_error, exact_distance, \
+                       root.{precision,recall,f1}")
+        )
+        .arg(
+            Arg::with_name(BOOTSTRAP)
+                .takes_value(true)
+                .long(BOOTSTRAP)
+                .value_name("N")
+                .help("Estimate 95% confidence intervals of UAS and LAS from N bootstrap \
+                       resamples of the sentences.")
+        )
+        .arg(
+            Arg::with_name(PREDICTION2)
+                .takes_value(true)
+                .long(PREDICTION2)
+                .value_name("FILE")
+                .help("Compare the LAS of PREDICTION and a second prediction FILE with a \
+                       paired bootstrap test. Uses the number of samples of --bootstrap \
+                       (default: 1000).")
+        )
+        .arg(
+            Arg::with_name(RUN)
+                .long(RUN)
+                .value_names(&["VALIDATION", "PREDICTION"])
+                .number_of_values(2)
+                .multiple(true)
+                .help("Evaluate a further run of the same experiment, such as another fold or \
+                       seed, and report the mean and standard deviation of every score over \
+                       VALIDATION/PREDICTION and all runs. Can be given multiple times.")
+        )
+        .arg(
+            Arg::with_name(SEED)
+                .takes_value(true)
+                .long(SEED)
+                .default_value("42")
+                .help("Seed of the random number generator.")
+        )
+        .arg(
+            Arg::with_name(PER_SENTENCE)
+                .takes_value(true)
+                .long(PER_SENTENCE)
+                .value_name("FILE")
+                .help("write per-sentence token counts, correct heads, correct labeled heads, \
+                       UAS and LAS to file. Sentences are identified by sent_id if every CoNLL-U \
+                       validation sentence has one, otherwise by their 0-based index.")
+        )
+        .arg(
+            Arg::with_name(LEARNING_CURVE)
+                .takes_value(true)
+                .long(LEARNING_CURVE)
+                .value_name("FILE")
+                .help("write the cumulative UAS and LAS of growing corpus prefixes to file, see \
+                       --curve-step and --curve-fractions. The last row covers all sentences.")
+        )
+        .arg(
+            Arg::with_name(CURVE_STEP)
+                .takes_value(true)
+                .long(CURVE_STEP)
+                .value_name("K")
+                .default_value("1000")
+                .help("report the cumulative scores after every K sentences")
+        )
+        .arg(
+            Arg::with_name(CURVE_FRACTIONS)
+                .takes_value(true)
+                .long(CURVE_FRACTIONS)
+                .value_name("FRACTIONS")
+                .help("report the cumulative scores after comma-separated fractions of the sentences \
+                       instead, e.g. 0.1,0.25,0.5")
+        )
+        .arg(
+            Arg::with_name(LAS_STRATA)
+                .takes_value(true)
+                .long(LAS_STRATA)
+                .value_name("FILE")
+                .help("write the number and fraction of sentences per LAS range (100%, 90-99%, ..., \
+                       50-59%, <50%) to file, sentences without scored tokens are counted as unscored")
+        )
+        .arg(
+            Arg::with_name(LAS_STRATA_SENTENCES)
+                .long(LAS_STRATA_SENTENCES)
+                .requires(LAS_STRATA)
+                .help("list the sentences of every LAS range, by sent_id if every CoNLL-U validation \
+                       sentence has one, otherwise by their 0-based index")
+        )
+        .arg(
+            Arg::with_name(LENGTH_BUCKETS)
+                .takes_value(true)
+                .long(LENGTH_BUCKETS)
+                .value_name("FILE")
+                .help("write UAS and LAS per sentence length bucket to file ('-' for stdout)")
+        )
+        .arg(
+            Arg::with_name(LENGTH_BOUNDS)
+                .takes_value(true)
+                .long(LENGTH_BOUNDS)
+                .value_name("BOUNDS")
+                .default_value("10,20,40")
+                .help("comma-separated inclusive upper bounds of the sentence length buckets")
+        )
+        .arg(
+            Arg::with_name(LABEL_FREQUENCY)
+                .takes_value(true)
+                .long(LABEL_FREQUENCY)
+                .value_name("FILE")
+                .help("write UAS and LAS of rare, medium and frequent gold relations to file, \
+                       see --label-frequency-bounds")
+        )
+        .arg(
+            Arg::with_name(LABEL_FREQUENCY_BOUNDS)
+                .takes_value(true)
+                .long(LABEL_FREQUENCY_BOUNDS)
+                .value_name("BOUNDS")
+                .default_value("10,100")
+                .help("comma-separated inclusive upper bounds of the gold relation frequency buckets")
+        )
+        .arg(
+            Arg::with_name(ATTACHMENT_ERRORS)
+                .takes_value(true)
+                .long(ATTACHMENT_ERRORS)
+                .value_name("FILE")
+                .help("write the number of wrong heads, total and error rate per gold relation to file")
+        )
+        .arg(
+            Arg::with_name(GENRE_REPORT)
+                .takes_value(true)
+                .long(GENRE_REPORT)
+                .value_name("FILE")
+                .conflicts_with(ALIGN_BY_SENT_ID)
+                .help("write UAS and LAS per sentence genre to file")
+        )
+        .arg(
+            Arg::with_name(GENRE_KEY)
+                .takes_value(true)
+                .long(GENRE_KEY)
+                .value_name("KEY")
+                .default_value("genre")
+                .help("comment key ('# KEY = genre') or token feature holding the sentence genre")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_SEP)
+                .takes_value(true)
+                .long(CONFUSION_SEP)
+                .value_name("SEP")
+                .help("separator of confusion matrix cells [default: tab]")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_HEADER)
+                .long(CONFUSION_HEADER)
+                .help("label the axes of confusion matrices as gold\\predicted in the corner cell")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_EXCLUDE_ROOT)
+                .long(CONFUSION_EXCLUDE_ROOT)
+                .help("leave the root (relation root, distance and direction ROOT) out of the accuracies \
+                       and precisions of confusion matrices, the counts are still written")
+        )
+        .arg(
+            Arg::with_name(WEIGHTS)
+                .takes_value(true)
+                .long(WEIGHTS)
+                .value_name("FILE")
+                .help("report UAS and LAS with tokens weighted by form, gold relation or gold POS tag \
+                       (see --weight-by), FILE holds KEY<TAB>WEIGHT lines (default weight: 1)")
+        )
+        .arg(
+            Arg::with_name(WEIGHT_BY)
+                .takes_value(true)
+                .long(WEIGHT_BY)
+                .possible_values(&["form", "rel", "pos"])
+                .default_value("form")
+                .help("key of the --weights file, pos uses the tag column of --pos-column")
+        )
+        .arg(
+            Arg::with_name(WEIGHT_FEATURE)
+                .takes_value(true)
+                .long(WEIGHT_FEATURE)
+                .value_name("NAME")
+                .conflicts_with(WEIGHTS)
+                .help("report UAS and LAS with tokens weighted by the numeric token feature NAME (default weight: 1)")
+        )
+        .arg(
+            Arg::with_name(ENHANCED)
+                .long(ENHANCED)
+                .conflicts_with_all(&[COLLAPSE_ENHANCED, ALIGN_BY_SENT_ID, PRECOUNT, PREDICTION2])
+                .help("score enhanced dependencies (DEPS column of CoNLL-U files) as sets of arcs: labeled (ELAS) and \
+                       unlabeled (EUAS) precision, recall and F1")
+        )
+        .arg(
+            Arg::with_name(CHECKPOINTS)
+                .long(CHECKPOINTS)
+                .conflicts_with_all(&[ENHANCED, SEGMENTATION, BROWSE, ALIGN_BY_SENT_ID, PRECOUNT, PREDICTION2, RUN])
+                .help("select the best checkpoint: evaluate every file in the PREDICTION directory \
+                       against the VALIDATION file, print a table of the scores per file and the \
+                       file with the best --select-metric score")
+        )
+        .arg(
+            Arg::with_name(SELECT_METRIC)
+                .takes_value(true)
+                .long(SELECT_METRIC)
+                .value_name("METRIC")
+                .possible_values(&[
+                    "uas",
+                    "las",
+                    "la",
+                    "uuas",
+                    "clas",
+                    "macro_las",
+                    "uem",
+                    "lem",
+                    "pos_accuracy",
+                    "lemma_accuracy",
+                    "mean_distance_error",
+                    "median_distance_error",
+                    "exact_distance",
+                    "root_precision",
+                    "root_recall",
+                    "root_f1",
+                ])
+                .default_value("las")
+                .help("Metric used to select the best checkpoint, lower is better for the \
+                       distance errors.")
+        )
+        .arg(
+            Arg::with_name(BROWSE)
+                .long(BROWSE)
+                .conflicts_with_all(&[ENHANCED, SEGMENTATION, ALIGN_BY_SENT_ID, PRECOUNT, PREDICTION2])
+                .help("browse the sentences with attachment errors interactively: gold and predicted \
+                       trees are shown side by side, commands are read from stdin. Sentences can be \
+                       filtered by the gold relation or the type (head, label) of their errors.")
+        )
+        .arg(
+            Arg::with_name(SEGMENTATION)
+                .long(SEGMENTATION)
+                .conflicts_with_all(&[ENHANCED, ALIGN_BY_SENT_ID, PRECOUNT, PREDICTION2, AGREEMENT])
+                .help("score predictions with a different sentence and word segmentation: align \
+                       words by character offsets and report sentence, word, UAS and LAS precision, \
+                       recall and F1. Both inputs must have the same characters, apart from whitespace.")
+        )
+        .arg(
+            Arg::with_name(FEATS_ACCURACY)
+                .takes_value(true)
+                .long(FEATS_ACCURACY)
+                .value_name("FILE")
+                .help("write precision and recall per morphological feature and the exact match rate to file")
+        )
+        .arg(
+            Arg::with_name(POS_ACCURACIES)
+                .takes_value(true)
+                .long(POS_ACCURACIES)
+                .value_name("FILE")
+                .help("write support, UAS and LAS per gold POS tag to file")
+        )
+        .arg(
+            Arg::with_name(ROOT_PRF)
+                .takes_value(true)
+                .long(ROOT_PRF)
+                .value_name("FILE")
+                .help("print root identification counts and precision, recall and F1 to file")
+        )
+        .arg(
+            Arg::with_name(TARGET_REL)
+                .takes_value(true)
+                .long(TARGET_REL)
+                .value_name("REL")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Print unlabeled and labeled arc precision, recall and F1 of the relation REL. \
+                       A labeled arc is correct if head and relation match, an unlabeled arc if the \
+                       head matches. Can be given several times.")
+        )
         .arg(
             Arg::with_name(DISTANCE_CONFUSION)
                 .takes_value(true)
@@ -153,6 +1573,55 @@ fn parse_args() -> ArgMatches<'static> {
                 .long(DISTANCE_ACCURACIES)
                 .help("print DISTANCE_ACCURACIES to file")
         )
+        .arg(
+            Arg::with_name(DISTANCE_PAIRS)
+                .takes_value(true)
+                .long(DISTANCE_PAIRS)
+                .value_name("FILE")
+                .help("write the non-zero cells of the distance confusion matrix as gold, predicted \
+                       and count TSV rows with a header to file")
+        )
+        .arg(
+            Arg::with_name(DISTANCE_BUCKETS)
+                .takes_value(true)
+                .long(DISTANCE_BUCKETS)
+                .value_name("BOUNDS")
+                .help("bucket head distances by comma-separated inclusive upper bounds, \
+                       e.g. 1,2,3,5,10 gives 1, 2, 3, 4-5, 6-10, 11+. Tokens attached to the \
+                       root are always labeled ROOT.")
+        )
+        .arg(
+            Arg::with_name(DISTANCE_DIRECTION)
+                .takes_value(true)
+                .long(DISTANCE_DIRECTION)
+                .value_name("FILE")
+                .help("Write head attachment errors per gold distance bucket (see --distance-buckets) \
+                       and attachment direction to FILE. Root attachments have their own ROOT row.")
+        )
+        .arg(
+            Arg::with_name(DISTANCE_ERROR_HIST)
+                .takes_value(true)
+                .long(DISTANCE_ERROR_HIST)
+                .value_name("FILE")
+                .help("write a histogram of the signed head distance error (predicted - gold distance) \
+                       to file ('-' for stdout)")
+        )
+        .arg(
+            Arg::with_name(DISTANCE_ERROR_MAX)
+                .takes_value(true)
+                .long(DISTANCE_ERROR_MAX)
+                .value_name("N")
+                .default_value("3")
+                .help("largest distance error with its own histogram bucket, larger errors are \
+                       counted as N")
+        )
+        .arg(
+            Arg::with_name(DIRECTION_CONFUSION)
+                .takes_value(true)
+                .long(DIRECTION_CONFUSION)
+                .value_name("FILE")
+                .help("print attachment direction (left/right/root) confusion matrix to file")
+        )
         .arg(
             Arg::with_name(DEPREL_ACCURACIES)
                 .takes_value(true)
@@ -164,156 +1633,437 @@ fn parse_args() -> ArgMatches<'static> {
                 .long(SKIP_PUNCTUATION)
                 .help("Ignore punctuation.")
         )
-        .get_matches()
-}
-
-pub trait GetFeature {
-    fn get_feature(&self, name: &str) -> Option<&str>;
-}
-
-impl GetFeature for Token {
-    fn get_feature(&self, name: &str) -> Option<&str> {
-        if let Some(features) = self.features() {
-            if let Some(feature) = features.as_map().get(name) {
-                return feature.as_ref().map(|f| f.as_str())
-            }
-        }
-        None
-    }
-}
-
-pub struct Confusion<V> {
-    confusion: Vec<Vec<usize>>,
-    numberer: Numberer<V>,
-    name: String,
-}
-
-impl<V> Confusion<V> where V: Clone + Hash + Eq {
-    pub fn new(name: impl Into<String>) -> Self {
-        Confusion {
-            confusion: Vec::new(),
-            numberer: Numberer::new(),
-            name: name.into(),
-        }
-    }
-
-    pub fn insert<S>(&mut self, target: S, prediction: S) where S: Into<V> {
-        let target_idx = self.numberer.number(target);
-        let pred_idx = self.numberer.number(prediction);
-        while target_idx >= self.confusion.len() || pred_idx >= self.confusion.len() {
-            self.confusion.push(vec![0; self.confusion.len()]);
-            self.confusion
-                .iter_mut()
-                .for_each(|row| row.push(0));
-        }
-        self.confusion[target_idx][pred_idx] += 1;
-    }
-}
-impl<V> Confusion<V> {
-    pub fn numberer(&self) -> &Numberer<V> {
-        &self.numberer
-    }
-}
-
-impl<V> Confusion<V> where V: ToString {
-
-    fn write_accuracies(&self, mut w: impl Write) -> Result<(), Error> {
-        for (idx, item) in self.numberer.idx2val.iter().map(V::to_string).enumerate() {
-            let row = &self.confusion[idx];
-            let correct = row[idx];
-            let total = row.iter().sum::<usize>();
-            let acc = correct as f32 / total as f32;
-            writeln!(w, "{}\t{}\t{:.04}", item, total, acc)?;
-        }
-        Ok(())
-    }
-
-    pub fn write_to_file(&self, mut w: impl Write, sep: &str) -> Result<(), Error> {
-        writeln!(w, "{}", self.numberer.idx2val.iter().map(ToString::to_string).join(sep))?;
-        for i in 0..self.confusion.len() {
-            writeln!(w, "{}", self.confusion[i].iter().map(|n| n.to_string()).join(sep))?;
-        }
-        Ok(())
-    }
-
-}
-
-impl<V> Display for Confusion<V> where V: ToString {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}\t{}", self.name, self.numberer.idx2val.iter().map(ToString::to_string).join("\t"))?;
-        let mut total_correct = 0;
-        let mut full_total = 0;
-        for (idx, val) in self.numberer.idx2val.iter().enumerate() {
-            let row = &self.confusion[idx];
-            let correct = row[idx];
-            total_correct += correct;
-            let total = row.iter().sum::<usize>();
-            full_total += total;
-            let acc = correct as f32 / total as f32;
-            writeln!(f, "{}\t{}\t{:.4}", val.to_string(), self.confusion[idx].iter().map(|n| n.to_string()).join("\t"), acc)?;
-        }
-        let mut delim = String::new();
-        let mut precs = String::new();
-        for i in 0..self.confusion.len() {
-            let mut false_pos = 0;
-            for j in 0..self.confusion.len() {
-                if j == i {
-                    continue
-                }
-                false_pos += self.confusion[j][i]
-            }
-            let prec = self.confusion[i][i] as f32 / (self.confusion[i][i] + false_pos) as f32;
-            precs.push_str(&format!("\t{:.4}", prec));
-            delim.push_str("\t____");
-        }
-        writeln!(f, "{}", delim)?;
-        writeln!(f, "{}", precs)?;
-        let acc = total_correct as f32 / full_total as f32;
-        writeln!(f, "acc: {:.4}", acc)?;
-        Ok(())
-    }
-}
-
-pub struct Numberer<V>{
-    val2idx: HashMap<V, usize>,
-    idx2val: Vec<V>,
-}
-
-impl<V> Numberer<V> where V: Clone + Hash + Eq {
-    pub fn new() -> Self {
-        Numberer {
-            val2idx: HashMap::new(),
-            idx2val: Vec::new(),
-        }
-    }
+        .arg(
+            Arg::with_name(ERRORS)
+                .takes_value(true)
+                .long(ERRORS)
+                .alias("errors-out")
+                .value_name("FILE")
+                .help("Log tokens with a wrong head or relation to FILE. Columns: sentence, \
+                       token, form, gold head, gold relation, predicted head, predicted relation.")
+        )
+        .arg(
+            Arg::with_name(ERROR_SENTENCES)
+                .takes_value(true)
+                .long(ERROR_SENTENCES)
+                .value_name("FILE")
+                .help("Write every sentence with a wrong head or relation as CoNLL-U to FILE, \
+                       preceded by '# sentence = INDEX' and '# error_tokens = TOKEN,...' comments. \
+                       Predictions are stored in MISC as with --merged-out.")
+        )
+        .arg(
+            Arg::with_name(TOP_N_ERRORS_PER_SENTENCE)
+                .takes_value(true)
+                .long(TOP_N_ERRORS_PER_SENTENCE)
+                .value_name("K")
+                .requires(ERRORS)
+                .help("Only log the first K errors of every sentence to the error log.")
+        )
+        .arg(
+            Arg::with_name(MERGED_OUT)
+                .takes_value(true)
+                .long(MERGED_OUT)
+                .value_name("FILE")
+                .help("Write the gold data as CoNLL-U to FILE, with the predicted head and \
+                       relation of every token in MISC as pred_head=HEAD|pred_rel=REL.")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_EXAMPLES)
+                .takes_value(true)
+                .long(CONFUSION_EXAMPLES)
+                .value_name("DIR")
+                .help("Write example tokens of the most frequent relation confusions to DIR, one \
+                       RANK-GOLD-PREDICTED.tsv file per confusion. Columns: sentence, token and the \
+                       sentence with the token marked as [[FORM]].")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_EXAMPLE_PAIRS)
+                .takes_value(true)
+                .long(CONFUSION_EXAMPLE_PAIRS)
+                .value_name("K")
+                .default_value("10")
+                .help("Write examples of the K most frequent relation confusions.")
+        )
+        .arg(
+            Arg::with_name(CONFUSION_EXAMPLE_COUNT)
+                .takes_value(true)
+                .long(CONFUSION_EXAMPLE_COUNT)
+                .value_name("N")
+                .default_value("5")
+                .help("Write the first N examples of every relation confusion.")
+        )
+        .arg(
+            Arg::with_name(TOP_CONFUSIONS)
+                .takes_value(true)
+                .long(TOP_CONFUSIONS)
+                .value_name("N")
+                .help("Print the N most frequent (gold, predicted) relation confusions.")
+        )
+        .arg(
+            Arg::with_name(SPURIOUS_RELS)
+                .long(SPURIOUS_RELS)
+                .help("List predicted relations that never occur in the validation data, \
+                       with their prediction counts.")
+        )
+        .arg(
+            Arg::with_name(PRECISION)
+                .takes_value(true)
+                .long(PRECISION)
+                .value_name("N")
+                .default_value("4")
+                .help("Number of decimal places of all reported scores.")
+        )
+        .arg(
+            Arg::with_name(ROUNDING)
+                .takes_value(true)
+                .long(ROUNDING)
+                .possible_values(&["half-up", "half-even", "trunc"])
+                .default_value("half-even")
+                .help("Rounding mode for all reported metrics.")
+        )
+        .arg(
+            Arg::with_name(POS_COLUMN)
+                .takes_value(true)
+                .long(POS_COLUMN)
+                .possible_values(&["upos", "xpos"])
+                .default_value("xpos")
+                .help("Tag column for POS accuracy and --pos-accuracies, upos is the CPOS column in CoNLL-X.")
+        )
+        .arg(
+            Arg::with_name(PRECOUNT)
+                .long(PRECOUNT)
+                .help("Count sentences and tokens in both files before scoring and \
+                       fail early if they differ. Requires an additional pass over the data.")
+        )
+        .arg(
+            Arg::with_name(CONLLU)
+                .long(CONLLU)
+                .help("Read both files as CoNLL-U. Comments, multiword token ranges and \
+                       empty nodes are skipped.")
+        )
+        .arg(
+            Arg::with_name(NO_ID_CHECK)
+                .long(NO_ID_CHECK)
+                .help("Do not check that the token ids of every CoNLL-U sentence are 1..n. \
+                       CoNLL-X token ids are never checked.")
+        )
+        .arg(
+            Arg::with_name(ROOT_HEAD)
+                .takes_value(true)
+                .long(ROOT_HEAD)
+                .value_name("VALUE")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .help("HEAD value of tokens attached to the root, an integer or 'self' for the \
+                       token's own id. Negative values require CoNLL-U input.")
+        )
+        .arg(
+            Arg::with_name(COLLAPSE_ENHANCED)
+                .long(COLLAPSE_ENHANCED)
+                .help("Read both files as CoNLL-U and score the prediction's enhanced \
+                       dependencies against basic gold dependencies. The first DEPS arc \
+                       of each predicted token that is not headed by an empty node is \
+                       used as its basic head and relation.")
+        )
+        .arg(
+            Arg::with_name(NO_PUNCT)
+                .long(NO_PUNCT)
+                .help("Exclude punctuation from UAS and LAS. A token is punctuation if its \
+                       gold relation is 'punct' or its gold tag is in --punct-tags.")
+        )
+        .arg(
+            Arg::with_name(NO_PUNCT_CONFUSION)
+                .long(NO_PUNCT_CONFUSION)
+                .requires(NO_PUNCT)
+                .help("Also exclude punctuation from the confusion matrices.")
+        )
+        .arg(
+            Arg::with_name(PUNCT_TAGS)
+                .takes_value(true)
+                .long(PUNCT_TAGS)
+                .value_name("TAGS")
+                .requires(NO_PUNCT)
+                .help("Comma-separated list of punctuation (C)POS tags.")
+        )
+        .arg(
+            Arg::with_name(GZIP)
+                .long(GZIP)
+                .help("Decompress all inputs with gzip. Files with the .gz extension are always decompressed.")
+        )
+        .arg(
+            Arg::with_name(ALIGN_BY_SENT_ID)
+                .long(ALIGN_BY_SENT_ID)
+                .conflicts_with(PRECOUNT)
+                .help("Pair sentences by their sent_id comments rather than by position (CoNLL-U only). \
+                       Unmatched sentences are reported and not scored.")
+        )
+        .arg(
+            Arg::with_name(IGNORE_CASE_FORMS)
+                .long(IGNORE_CASE_FORMS)
+                .help("Compare validation and prediction forms case-insensitively.")
+        )
+        .arg(
+            Arg::with_name(NO_FORM_CHECK)
+                .long(NO_FORM_CHECK)
+                .conflicts_with(IGNORE_CASE_FORMS)
+                .help("Do not check that validation and prediction forms match.")
+        )
+        .arg(
+            Arg::with_name(SKIP_MISMATCHED)
+                .long(SKIP_MISMATCHED)
+                .help("Skip sentences whose lengths or forms differ and validation tokens \
+                       without head or relation instead of failing. The number of skipped \
+                       sentences and tokens is reported per reason.")
+        )
+        .arg(
+            Arg::with_name(THREADS)
+                .takes_value(true)
+                .long(THREADS)
+                .value_name("N")
+                .default_value("1")
+                .help("Number of threads used for scoring.")
+        )
+        .arg(
+            Arg::with_name(SENTENCE_RANGE)
+                .takes_value(true)
+                .long(SENTENCE_RANGE)
+                .value_name("START:END")
+                .conflicts_with(ALIGN_BY_SENT_ID)
+                .help("Only score the sentences with 0-based index START (inclusive) to END \
+                       (exclusive). The other sentences are still read to keep VALIDATION and \
+                       PREDICTION aligned.")
+        )
+        .arg(
+            Arg::with_name(SAMPLE_RATE)
+                .takes_value(true)
+                .long(SAMPLE_RATE)
+                .alias("sample")
+                .value_name("RATE")
+                .help("Score a random sample of the sentences, each sentence is scored with probability \
+                       RATE. Use --seed to change the sample.")
+        )
+        .arg(
+            Arg::with_name(SKIP)
+                .takes_value(true)
+                .long(SKIP)
+                .value_name("N")
+                .default_value("0")
+                .help("Skip the first N sentences of VALIDATION and PREDICTION.")
+        )
+        .arg(
+            Arg::with_name(MAX_SENTENCES)
+                .takes_value(true)
+                .long(MAX_SENTENCES)
+                .conflicts_with(SENTENCE_RANGE)
+                .value_name("M")
+                .help("Stop after M sentences (after the skipped sentences). The remaining \
+                       sentences are not read, so a different number of sentences in \
+                       VALIDATION and PREDICTION is not detected.")
+        )
+        .arg(
+            Arg::with_name(PROGRESS)
+                .long(PROGRESS)
+                .help("Report the number of sentences and the UAS so far to stderr during evaluation.")
+        )
+        .arg(
+            Arg::with_name(PROGRESS_INTERVAL)
+                .takes_value(true)
+                .long(PROGRESS_INTERVAL)
+                .value_name("N")
+                .default_value("10000")
+                .help("Report progress every N sentences.")
+        )
+        .arg(
+            Arg::with_name(PROJECTIVITY)
+                .long(PROJECTIVITY)
+                .help("Print UAS and LAS of tokens with projective and non-projective gold edges, \
+                       and of sentences without and with non-projective gold edges.")
+        )
+        .arg(
+            Arg::with_name(REFERENCE)
+                .takes_value(true)
+                .long(REFERENCE)
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .help("A further gold annotation of the text of VALIDATION. Prints the scores \
+                       against every reference and best-reference UAS and LAS over the scored tokens, \
+                       counting a prediction as correct if it matches any reference. Can be \
+                       given multiple times.")
+        )
+        .arg(
+            Arg::with_name(SORT_LABELS)
+                .long(SORT_LABELS)
+                .help("Order the rows and columns of the confusion matrices by label instead of \
+                       by first occurrence, so that reordering the input does not change the \
+                       outputs.")
+        )
+        .arg(
+            Arg::with_name(STATISTICS)
+                .long(STATISTICS)
+                .help("Print corpus statistics: the number of sentences and tokens, the mean \
+                       sentence length, the number of non-projective gold arcs and the gold \
+                       relation distribution.")
+        )
+        .arg(
+            Arg::with_name(IGNORE_RELS)
+                .takes_value(true)
+                .long(IGNORE_RELS)
+                .value_name("RELS")
+                .help("Comma-separated list of gold relations to exclude from scores and confusion matrices.")
+        )
+        .arg(
+            Arg::with_name(IGNORE_ROOT_REL)
+                .long(IGNORE_ROOT_REL)
+                .help("Do not require a matching relation for LAS if the gold head is the \
+                       root. Reports the number of root tokens whose relation was ignored.")
+        )
+        .arg(
+            Arg::with_name(COARSE_RELS)
+                .long(COARSE_RELS)
+                .help("Strip relation subtypes (everything from the first ':') before \
+                       computing LAS and the deprel confusion matrix.")
+        )
+        .arg(
+            Arg::with_name(REL_RULES)
+                .takes_value(true)
+                .long(REL_RULES)
+                .value_name("FILE")
+                .help("Rewrite relations according to the rules in FILE before scoring. \
+                       One rule per line: PATTERN<TAB>REPLACEMENT. A PATTERN ending in '*' \
+                       matches every relation with that prefix, any other PATTERN matches \
+                       exactly. The first matching rule wins, unmatched relations are kept.")
+        )
+        .arg(
+            Arg::with_name(REL_MAP)
+                .takes_value(true)
+                .long(REL_MAP)
+                .value_name("FILE")
+                .conflicts_with(REL_RULES)
+                .help("Map gold and predicted relations according to FILE before scoring. \
+                       One mapping per line: FROM<TAB>TO. Unlisted relations are kept.")
+        )
+        .arg(
+            Arg::with_name(CLAUSE_IDS)
+                .takes_value(true)
+                .long(CLAUSE_IDS)
+                .value_name("FILE")
+                .help("Derive predicted relations from the 'clause_id' feature of the \
+                       predicted tokens (clause_id=ID or clause_id:ID) rather than using \
+                       the predicted relations. FILE maps clause ids to relations, one \
+                       mapping per line: CLAUSE_ID<TAB>RELATION. Tokens without the \
+                       feature or with an unlisted clause id get the relation '_'. \
+                       Affects LAS and the deprel confusion matrix.")
+        );
 
-    fn number<S>(&mut self, val: S) -> usize where S: Into<V> {
-        let val = val.into();
-        if let Some(idx) = self.val2idx.get(&val) {
-            *idx
-        } else {
-            let n_vals = self.val2idx.len();
-            self.val2idx.insert(val.clone(), n_vals);
-            self.idx2val.push(val);
-            n_vals
-        }
-    }
+    #[cfg(feature = "serde")]
+    let app = app
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name(DEPREL_CONFUSION_JSON)
+                .takes_value(true)
+                .long(DEPREL_CONFUSION_JSON)
+                .value_name("FILE")
+                .help("Write the deprel confusion matrix as JSON to FILE, see the merge subcommand.")
+        )
+        .subcommand(
+            SubCommand::with_name(MERGE)
+                .about("Merge confusion matrices written with --deprel-confusion-json")
+                .arg(
+                    Arg::with_name(MATRICES)
+                        .help("JSON confusion matrices")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(SORT_LABELS)
+                        .long(SORT_LABELS)
+                        .help("Order the merged matrix by label instead of by first occurrence.")
+                )
+                .arg(
+                    Arg::with_name(JSON_OUT)
+                        .takes_value(true)
+                        .long(JSON_OUT)
+                        .value_name("FILE")
+                        .help("Write the merged matrix as JSON to FILE.")
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CONFUSION)
+                .about("Post-process a confusion matrix written with --deprel-confusion-json")
+                .arg(
+                    Arg::with_name(MATRIX)
+                        .help("JSON confusion matrix")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(MERGE_WITH)
+                        .takes_value(true)
+                        .long(MERGE_WITH)
+                        .value_name("FILE")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Merge the JSON confusion matrix FILE into MATRIX. Can be given \
+                               multiple times.")
+                )
+                .arg(
+                    Arg::with_name(PRUNE)
+                        .takes_value(true)
+                        .long(PRUNE)
+                        .value_name("N")
+                        .help("Remove values that occur less than N times both as gold and as \
+                               predicted value.")
+                )
+                .arg(
+                    Arg::with_name(SORT_LABELS)
+                        .long(SORT_LABELS)
+                        .help("Order the matrix by label instead of by first occurrence.")
+                )
+                .arg(
+                    Arg::with_name(CONFUSION_OUTPUT)
+                        .takes_value(true)
+                        .long(CONFUSION_OUTPUT)
+                        .possible_values(&["matrix", "accuracies", "normalized", "prf", "json"])
+                        .default_value("matrix")
+                        .help("Write the matrix, the accuracies, the column-normalized matrix, \
+                               precision/recall/F1 per value or the JSON matrix to stdout.")
+                )
+                .arg(
+                    Arg::with_name(CONFUSION_SEP)
+                        .takes_value(true)
+                        .long(CONFUSION_SEP)
+                        .value_name("SEP")
+                        .help("separator of confusion matrix cells [default: tab]")
+                )
+                .arg(
+                    Arg::with_name(CONFUSION_HEADER)
+                        .long(CONFUSION_HEADER)
+                        .help("label the axes of confusion matrices as gold\\predicted in the corner cell")
+                )
+                .arg(
+                    Arg::with_name(ROUNDING)
+                        .takes_value(true)
+                        .long(ROUNDING)
+                        .possible_values(&["half-up", "half-even", "trunc"])
+                        .default_value("half-even")
+                        .help("Rounding mode for all reported metrics.")
+                )
+                .arg(
+                    Arg::with_name(PRECISION)
+                        .takes_value(true)
+                        .long(PRECISION)
+                        .value_name("N")
+                        .default_value("4")
+                        .help("Number of decimal places of all reported scores.")
+                )
+                .arg(
+                    Arg::with_name(JSON_OUT)
+                        .takes_value(true)
+                        .long(JSON_OUT)
+                        .value_name("FILE")
+                        .help("Write the processed matrix as JSON to FILE.")
+                ),
+        );
 
-    pub fn get_number(&self, val: &V) -> Option<usize> {
-        self.val2idx.get(val).map(|idx| *idx)
-    }
+    app.get_matches()
 }
-
-impl<V> Numberer<V> {
-    pub fn len(&self) -> usize {
-        self.idx2val.len()
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.idx2val.is_empty()
-    }
-
-    pub fn get_val(&self, idx: usize) -> Option<&V> {
-        self.idx2val.get(idx)
-    }
-}
\ No newline at end of file
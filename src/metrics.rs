@@ -0,0 +1,293 @@
+use std::io::Write;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use failure::{format_err, Error};
+
+/// Rounding mode for reported metrics.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round ties away from zero, `0.87655` becomes `0.8766`.
+    HalfUp,
+    /// Round ties to the even digit, `0.87655` becomes `0.8766` and
+    /// `0.87645` becomes `0.8764`. Ties are decided on the decimal value,
+    /// not on its binary approximation.
+    #[default]
+    HalfEven,
+    /// Drop all digits beyond the precision, `0.87659` becomes `0.8765`.
+    Trunc,
+}
+
+impl Rounding {
+    /// Round `value` to `digits` decimal places.
+    ///
+    /// The result is meant to be formatted with the same precision, see
+    /// `format`.
+    pub fn apply(self, value: f32, digits: usize) -> f32 {
+        let scale = 10f64.powi(digits as i32);
+        // Strip the representation error of the single-precision value,
+        // otherwise e.g. 0.7 would be truncated to 0.6999.
+        let scaled = (value as f64 * scale * 1e3).round() / 1e3;
+        match self {
+            Rounding::HalfUp => (scaled.round() / scale) as f32,
+            Rounding::HalfEven => {
+                let floor = scaled.floor();
+                let rounded = if scaled - floor == 0.5 {
+                    floor + floor.rem_euclid(2.)
+                } else {
+                    scaled.round()
+                };
+                (rounded / scale) as f32
+            }
+            Rounding::Trunc => (scaled.trunc() / scale) as f32,
+        }
+    }
+
+    /// Format `value` with `digits` decimal places.
+    pub fn format(self, value: f32, digits: usize) -> String {
+        format!("{:.*}", digits, self.apply(value, digits))
+    }
+}
+
+/// Default number of decimal places of reported metrics.
+pub const DEFAULT_PRECISION: usize = 4;
+
+impl FromStr for Rounding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-up" => Ok(Rounding::HalfUp),
+            "half-even" => Ok(Rounding::HalfEven),
+            "trunc" => Ok(Rounding::Trunc),
+            _ => Err(format_err!("Unknown rounding mode: {}", s)),
+        }
+    }
+}
+
+/// Attachment counts of a group of tokens, such as a sentence.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AttachmentCounts {
+    /// Number of scored tokens.
+    pub total: usize,
+
+    /// Number of tokens with a correct head.
+    pub correct_head: usize,
+
+    /// Number of tokens with a correct head and relation.
+    pub correct_head_label: usize,
+}
+
+impl AttachmentCounts {
+    /// Count a token.
+    pub fn insert(&mut self, head_correct: bool, head_label_correct: bool) {
+        self.total += 1;
+        self.correct_head += head_correct as usize;
+        self.correct_head_label += head_label_correct as usize;
+    }
+
+    pub fn uas(&self) -> f32 {
+        ratio(self.correct_head, self.total)
+    }
+
+    pub fn las(&self) -> f32 {
+        ratio(self.correct_head_label, self.total)
+    }
+}
+
+impl AddAssign for AttachmentCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.total += other.total;
+        self.correct_head += other.correct_head;
+        self.correct_head_label += other.correct_head_label;
+    }
+}
+
+/// Root identification counts.
+///
+/// A token is a gold root if its gold head is the artificial root, and
+/// a predicted root if its predicted head is the artificial root. Since
+/// a prediction can have several roots or none at all, precision and
+/// recall can differ.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RootPrf {
+    pub gold: usize,
+    pub predicted: usize,
+    pub correct: usize,
+}
+
+impl AddAssign for RootPrf {
+    fn add_assign(&mut self, other: Self) {
+        self.gold += other.gold;
+        self.predicted += other.predicted;
+        self.correct += other.correct;
+    }
+}
+
+impl RootPrf {
+    pub fn insert(&mut self, gold_root: bool, pred_root: bool) {
+        self.gold += gold_root as usize;
+        self.predicted += pred_root as usize;
+        self.correct += (gold_root && pred_root) as usize;
+    }
+
+    pub fn precision(&self) -> f32 {
+        ratio(self.correct, self.predicted)
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(self.correct, self.gold)
+    }
+
+    pub fn f1(&self) -> f32 {
+        f_beta(self.precision(), self.recall(), 1.)
+    }
+
+    /// Write the counts and scores as `key<TAB>value` lines.
+    pub fn write(&self, mut w: impl Write, rounding: Rounding, precision: usize) -> Result<(), Error> {
+        writeln!(w, "gold\t{}", self.gold)?;
+        writeln!(w, "predicted\t{}", self.predicted)?;
+        writeln!(w, "correct\t{}", self.correct)?;
+        writeln!(w, "precision\t{}", rounding.format(self.precision(), precision))?;
+        writeln!(w, "recall\t{}", rounding.format(self.recall(), precision))?;
+        writeln!(w, "f1\t{}", rounding.format(self.f1(), precision))?;
+        Ok(())
+    }
+}
+
+/// Sentences and tokens that were skipped instead of scored.
+///
+/// Mismatched sentences are only skipped if
+/// `EvalOptions::skip_mismatched` is set, otherwise they are errors.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SkipCounts {
+    /// Sentences whose validation and prediction lengths differ.
+    pub length_mismatch: usize,
+
+    /// Sentences with a validation form that differs from the
+    /// prediction form.
+    pub form_mismatch: usize,
+
+    /// Tokens without a validation head or relation.
+    pub missing_annotation: usize,
+}
+
+impl AddAssign for SkipCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.length_mismatch += other.length_mismatch;
+        self.form_mismatch += other.form_mismatch;
+        self.missing_annotation += other.missing_annotation;
+    }
+}
+
+/// Arc counts of a single relation.
+///
+/// An arc is a gold arc if its gold relation is the relation and a
+/// predicted arc if its predicted relation is the relation. The labeled
+/// scores only count arcs with the relation on both sides and a correct
+/// head. The unlabeled scores count gold and predicted arcs with a
+/// correct head, regardless of the relation on the other side.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RelPrf {
+    pub gold: usize,
+    pub predicted: usize,
+    pub gold_correct_head: usize,
+    pub predicted_correct_head: usize,
+    pub correct: usize,
+}
+
+impl AddAssign for RelPrf {
+    fn add_assign(&mut self, other: Self) {
+        self.gold += other.gold;
+        self.predicted += other.predicted;
+        self.gold_correct_head += other.gold_correct_head;
+        self.predicted_correct_head += other.predicted_correct_head;
+        self.correct += other.correct;
+    }
+}
+
+impl RelPrf {
+    pub fn insert(&mut self, gold_rel: bool, pred_rel: bool, head_correct: bool) {
+        self.gold += gold_rel as usize;
+        self.predicted += pred_rel as usize;
+        self.gold_correct_head += (gold_rel && head_correct) as usize;
+        self.predicted_correct_head += (pred_rel && head_correct) as usize;
+        self.correct += (gold_rel && pred_rel && head_correct) as usize;
+    }
+
+    pub fn unlabeled_precision(&self) -> f32 {
+        ratio(self.predicted_correct_head, self.predicted)
+    }
+
+    pub fn unlabeled_recall(&self) -> f32 {
+        ratio(self.gold_correct_head, self.gold)
+    }
+
+    pub fn unlabeled_f1(&self) -> f32 {
+        f_beta(self.unlabeled_precision(), self.unlabeled_recall(), 1.)
+    }
+
+    pub fn precision(&self) -> f32 {
+        ratio(self.correct, self.predicted)
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(self.correct, self.gold)
+    }
+
+    pub fn f1(&self) -> f32 {
+        f_beta(self.precision(), self.recall(), 1.)
+    }
+}
+
+/// Divide two counts, returning 0 if the denominator is 0.
+///
+/// Used for all scores, so that empty inputs or inputs in which every
+/// token is filtered out give scores of 0 rather than `NaN`.
+pub fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+/// Weighted harmonic mean of precision and recall.
+pub fn f_beta(precision: f32, recall: f32, beta: f32) -> f32 {
+    let beta2 = beta * beta;
+    let denom = beta2 * precision + recall;
+    if denom == 0. {
+        0.
+    } else {
+        (1. + beta2) * precision * recall / denom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{f_beta, ratio, Rounding};
+
+    #[test]
+    fn half_even_rounds_ties_to_even() {
+        assert_eq!(Rounding::HalfEven.format(0.87655, 4), "0.8766");
+        assert_eq!(Rounding::HalfEven.format(0.87645, 4), "0.8764");
+        assert_eq!(Rounding::HalfEven.format(0.125, 2), "0.12");
+        assert_eq!(Rounding::HalfEven.format(0.135, 2), "0.14");
+        assert_eq!(Rounding::HalfEven.format(0.1251, 2), "0.13");
+    }
+
+    #[test]
+    fn half_up_and_trunc() {
+        assert_eq!(Rounding::HalfUp.format(0.87645, 4), "0.8765");
+        assert_eq!(Rounding::Trunc.format(0.87659, 4), "0.8765");
+        assert_eq!(Rounding::Trunc.format(0.7, 4), "0.7000");
+    }
+
+    #[test]
+    fn zero_denominators() {
+        assert_eq!(ratio(0, 0), 0.);
+        assert_eq!(ratio(1, 4), 0.25);
+        assert_eq!(f_beta(0., 0., 1.), 0.);
+        assert!((f_beta(0.5, 1., 1.) - 2. / 3.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,157 @@
+//! Evaluation against several gold references.
+//!
+//! Some texts have several legitimate annotations, e.g. in
+//! double-annotated sections of a treebank. A predicted head is correct
+//! if it is the head of the token in any reference, a predicted head and
+//! relation are correct if both are the same in any reference.
+
+use conllx::io::ReadSentence;
+use failure::{format_err, Error};
+
+use crate::eval::{check_sentence_pair, score_token, SentenceSelection, TokenDecision};
+use crate::{AttachmentCounts, EvalOptions};
+
+/// Score predictions against the best-matching of several references.
+///
+/// All references must have the same sentences as the prediction. The
+/// options are applied as in `evaluate_with`. Whether a token is scored,
+/// e.g. with `no_punct` or `ignore_rels`, is decided by the first
+/// reference. A sentence is skipped if it mismatches any reference.
+pub fn evaluate_best_reference<R>(
+    mut val_readers: Vec<R>,
+    mut pred_reader: impl ReadSentence,
+    options: &EvalOptions,
+) -> Result<AttachmentCounts, Error>
+where
+    R: ReadSentence,
+{
+    let mut counts = AttachmentCounts::default();
+    let mut selection = SentenceSelection::new(options);
+    let mut n_read = 0;
+    loop {
+        if selection.done(n_read) {
+            break;
+        }
+        let sentence_idx = n_read;
+        let pred_sentence = pred_reader.read_sentence()?;
+        let val_sentences = val_readers
+            .iter_mut()
+            .map(ReadSentence::read_sentence)
+            .collect::<Result<Vec<_>, _>>()?;
+        let pred_sentence = match pred_sentence {
+            Some(pred_sentence) if val_sentences.iter().all(Option::is_some) => pred_sentence,
+            None if val_sentences.iter().all(Option::is_none) => break,
+            _ => {
+                return Err(format_err!(
+                    "References and prediction differ in the number of sentences after sentence {}",
+                    sentence_idx
+                ))
+            }
+        };
+        let val_sentences = val_sentences.into_iter().flatten().collect::<Vec<_>>();
+        n_read += 1;
+        if selection.skip(sentence_idx) || !selection.sample() {
+            continue;
+        }
+
+        let mut mismatched = false;
+        for val_sentence in &val_sentences {
+            mismatched |= check_sentence_pair(sentence_idx, val_sentence, &pred_sentence, options)?.is_some();
+        }
+        if mismatched {
+            continue;
+        }
+
+        let pred_graph = pred_sentence.dep_graph();
+        for idx in 1..pred_sentence.len() {
+            let pred_token = pred_sentence[idx].token().expect("Non-root node without token");
+            let pred_triple = pred_graph.head(idx);
+            let mut scored = false;
+            let mut head_correct = false;
+            let mut head_label_correct = false;
+            for (ref_idx, val_sentence) in val_sentences.iter().enumerate() {
+                let val_token = val_sentence[idx].token().expect("Non-root node without token");
+                let val_triple = val_sentence.dep_graph().head(idx);
+                match score_token(sentence_idx, idx, val_token, pred_token, val_triple.as_ref(), pred_triple.as_ref(), options)? {
+                    TokenDecision::Scored(score) if ref_idx != 0 || !score.excluded => {
+                        scored = true;
+                        head_correct |= score.head_correct;
+                        head_label_correct |= score.head_correct && score.rel_correct;
+                    }
+                    _ if ref_idx == 0 => break,
+                    _ => (),
+                }
+            }
+            if scored {
+                counts.insert(head_correct, head_label_correct);
+            }
+        }
+    }
+    selection.check_range(n_read)?;
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate_best_reference;
+    use crate::conllu::Reader;
+    use crate::EvalOptions;
+
+    static GOLD: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tnsubj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t3\tpunct\t_\t_
+";
+
+    /// The relation of `dog` and the head of `.` are wrong.
+    static PRED: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tobj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t2\tpunct\t_\t_
+";
+
+    fn best(references: &[&str], pred: &str, options: &EvalOptions) -> (usize, usize, usize) {
+        let readers = references.iter().map(|reference| Reader::new(reference.as_bytes())).collect();
+        let counts = evaluate_best_reference(readers, Reader::new(pred.as_bytes()), options).unwrap();
+        (counts.total, counts.correct_head, counts.correct_head_label)
+    }
+
+    #[test]
+    fn any_reference_is_correct() {
+        let other = GOLD.replace("\tnsubj\t", "\tobj\t");
+        assert_eq!(best(&[GOLD], PRED, &EvalOptions::default()), (4, 3, 2));
+        assert_eq!(best(&[GOLD, &other], PRED, &EvalOptions::default()), (4, 3, 3));
+    }
+
+    #[test]
+    fn options_are_applied() {
+        let options = EvalOptions {
+            no_punct: true,
+            ..EvalOptions::default()
+        };
+        assert_eq!(best(&[GOLD], PRED, &options), (3, 3, 2));
+
+        let options = EvalOptions {
+            coarse_rels: true,
+            ..EvalOptions::default()
+        };
+        let subtyped = PRED.replace("\tobj\t", "\tnsubj:pass\t");
+        assert_eq!(best(&[GOLD], &subtyped, &options), (4, 3, 3));
+    }
+
+    #[test]
+    fn missing_relation_is_wrong() {
+        let pred = PRED.replace("\tdet\t", "\t_\t");
+        let gold = GOLD.replace("\tdet\t", "\t_\t");
+        let options = EvalOptions {
+            skip_mismatched: true,
+            ..EvalOptions::default()
+        };
+        // The first token has no gold relation and is not scored.
+        assert_eq!(best(&[&gold, GOLD], &pred, &options), (3, 2, 1));
+        // A missing predicted relation is a labeled error.
+        assert_eq!(best(&[GOLD], &pred, &options), (4, 3, 1));
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use failure::{format_err, Error};
+
+/// Relation rewriting rules.
+///
+/// Rules are tried in file order, the first rule whose pattern matches
+/// a relation determines its replacement. A pattern ending in `*` is a
+/// prefix pattern, e.g. `nmod:*` matches `nmod:poss` and `nmod:tmod` but
+/// not `nmod` itself. All other patterns have to match exactly, which
+/// allows to protect single subtypes from a more general rule by listing
+/// them first (columns are tab-separated):
+///
+/// ```text
+/// nsubj:pass  nsubj:pass
+/// nsubj:*     nsubj
+/// nmod:*      nmod
+/// ```
+#[derive(Clone, Debug)]
+pub struct RelRules {
+    rules: Vec<(RelPattern, String)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum RelPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl RelPattern {
+    fn matches(&self, rel: &str) -> bool {
+        match self {
+            RelPattern::Exact(pattern) => rel == pattern,
+            RelPattern::Prefix(prefix) => rel.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+impl RelRules {
+    /// Read rules from a file.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        Self::read(path, true)
+    }
+
+    /// Read a relation mapping from a file.
+    ///
+    /// Every line holds a `FROM<TAB>TO` pair, relations that are not
+    /// listed are kept. Unlike in rule files, `*` has no special meaning
+    /// and every relation can only be mapped once. Empty lines and lines
+    /// starting with `#` are ignored.
+    pub fn from_map_file(path: &str) -> Result<Self, Error> {
+        Self::read(path, false)
+    }
+
+    fn read(path: &str, prefix_patterns: bool) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut rules = Vec::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let mut parts = line.split('\t');
+            let (pattern, replacement) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(pattern), Some(replacement), None) => (pattern.trim(), replacement.trim()),
+                _ if prefix_patterns => return Err(format_err!("{}:{}: expected PATTERN<TAB>REPLACEMENT", path, line_no + 1)),
+                _ => return Err(format_err!("{}:{}: expected FROM<TAB>TO", path, line_no + 1)),
+            };
+            let pattern = match pattern.strip_suffix('*') {
+                Some(prefix) if prefix_patterns => RelPattern::Prefix(prefix.to_owned()),
+                _ => RelPattern::Exact(pattern.to_owned()),
+            };
+            if !prefix_patterns && rules.iter().any(|(other, _)| *other == pattern) {
+                return Err(format_err!("{}:{}: relation is mapped more than once", path, line_no + 1));
+            }
+            rules.push((pattern, replacement.to_owned()));
+        }
+        Ok(RelRules { rules })
+    }
+
+    /// Rewrite a relation with the first matching rule.
+    pub fn apply<'a>(&'a self, rel: &'a str) -> &'a str {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(rel))
+            .map(|(_, replacement)| replacement.as_str())
+            .unwrap_or(rel)
+    }
+}
+
+/// Strip the subtype from a relation, e.g. `nsubj:pass` becomes `nsubj`.
+///
+/// Relations without subtype are returned unchanged.
+pub fn strip_subtype(rel: &str) -> &str {
+    match rel.find(':') {
+        Some(idx) => &rel[..idx],
+        None => rel,
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use conllx::graph::{DepTriple, Sentence};
+use conllx::io::ReadSentence;
+use failure::{format_err, Error};
+
+/// HEAD value of tokens attached to the artificial root.
+///
+/// Standard CoNLL-X and CoNLL-U use `0`, some converters use `-1` or the
+/// id of the token itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RootHead {
+    /// A fixed HEAD value.
+    Value(i64),
+    /// The id of the token itself.
+    Token,
+}
+
+impl Default for RootHead {
+    fn default() -> Self {
+        RootHead::Value(0)
+    }
+}
+
+impl FromStr for RootHead {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "self" {
+            return Ok(RootHead::Token);
+        }
+        s.parse()
+            .map(RootHead::Value)
+            .map_err(|_| format_err!("Cannot parse root head: {}", s))
+    }
+}
+
+impl RootHead {
+    /// Check whether the HEAD field `head` of the token `dependent`
+    /// marks an attachment to the root.
+    pub fn is_root(self, head: &str, dependent: usize) -> bool {
+        match self {
+            RootHead::Value(value) => head.parse::<i64>() == Ok(value),
+            RootHead::Token => head.parse::<usize>() == Ok(dependent),
+        }
+    }
+
+    /// Attach every token whose head marks the root to the artificial
+    /// root.
+    ///
+    /// Only heads that could be read are rewritten, negative values
+    /// cannot be read from CoNLL-X files.
+    pub fn normalize(self, sentence: &mut Sentence) {
+        if self == RootHead::default() {
+            return;
+        }
+        for dependent in 1..sentence.len() {
+            let triple = match sentence.dep_graph().head(dependent) {
+                Some(triple) if self.is_root(&triple.head().to_string(), dependent) => {
+                    DepTriple::new(0, triple.relation().map(ToOwned::to_owned), dependent)
+                }
+                _ => continue,
+            };
+            sentence.dep_graph_mut().add_deprel(triple);
+        }
+    }
+}
+
+/// A reader that attaches tokens to the root according to a `RootHead`.
+pub struct RootHeadReader<R> {
+    reader: R,
+    root_head: RootHead,
+}
+
+impl<R> RootHeadReader<R> {
+    pub fn new(reader: R, root_head: RootHead) -> Self {
+        RootHeadReader { reader, root_head }
+    }
+}
+
+impl<R: ReadSentence> ReadSentence for RootHeadReader<R> {
+    fn read_sentence(&mut self) -> Result<Option<Sentence>, Error> {
+        Ok(self.reader.read_sentence()?.map(|mut sentence| {
+            self.root_head.normalize(&mut sentence);
+            sentence
+        }))
+    }
+}
@@ -0,0 +1,284 @@
+//! Evaluation of predictions with a different segmentation.
+//!
+//! Raw-text pipelines split the text into sentences and words themselves,
+//! so the predicted sentences and tokens need not correspond to the gold
+//! sentences and tokens. Both inputs are therefore read as a single token
+//! stream and aligned by character offsets, ignoring whitespace, as in the
+//! CoNLL 2018 shared task. A predicted word is correct if it spans exactly
+//! the characters of a gold word, a predicted sentence is correct if it
+//! spans exactly the characters of a gold sentence. The head of a correct
+//! word is correct if it is the root in both inputs or if both heads span
+//! the same characters.
+
+use std::collections::{BTreeSet, HashMap};
+
+use conllx::io::ReadSentence;
+use failure::{format_err, Error};
+
+use crate::metrics::{f_beta, ratio};
+
+/// Start and end (exclusive) character offset.
+type Span = (usize, usize);
+
+/// A token of a token stream.
+struct StreamToken {
+    span: Span,
+
+    /// Stream position of the head, 0 for the root and 1 for the first
+    /// token of the stream.
+    head: Option<usize>,
+
+    relation: Option<String>,
+}
+
+/// The tokens of all sentences of a reader.
+struct TokenStream {
+    /// The characters of all forms, without whitespace.
+    text: Vec<char>,
+
+    tokens: Vec<StreamToken>,
+
+    sentences: BTreeSet<Span>,
+}
+
+impl TokenStream {
+    fn read(mut reader: impl ReadSentence) -> Result<Self, Error> {
+        let mut stream = TokenStream {
+            text: Vec::new(),
+            tokens: Vec::new(),
+            sentences: BTreeSet::new(),
+        };
+        let mut sentence_idx = 0;
+        while let Some(sentence) = reader.read_sentence()? {
+            sentence_idx += 1;
+            let offset = stream.tokens.len();
+            let sentence_start = stream.text.len();
+            let graph = sentence.dep_graph();
+            for (idx, token) in sentence.iter().enumerate().filter_map(|(idx, node)| node.token().map(|t| (idx, t))) {
+                let triple = graph.head(idx);
+                if let Some(head) = triple.as_ref().map(|triple| triple.head()) {
+                    if head >= sentence.len() {
+                        return Err(format_err!(
+                            "Sentence {}, token {}: head {} is out of range",
+                            sentence_idx,
+                            idx,
+                            head
+                        ));
+                    }
+                }
+                let start = stream.text.len();
+                stream.text.extend(token.form().chars().filter(|c| !c.is_whitespace()));
+                if stream.text.len() == start {
+                    // Words are identified by their span, so every word
+                    // must span at least one character.
+                    return Err(format_err!("Sentence {}, token {}: empty form", sentence_idx, idx));
+                }
+                stream.tokens.push(StreamToken {
+                    span: (start, stream.text.len()),
+                    head: triple.as_ref().map(|triple| match triple.head() {
+                        0 => 0,
+                        head => offset + head,
+                    }),
+                    relation: triple.as_ref().and_then(|triple| triple.relation()).map(ToOwned::to_owned),
+                });
+            }
+            stream.sentences.insert((sentence_start, stream.text.len()));
+        }
+        Ok(stream)
+    }
+
+    /// The span of a head, `None` for the root.
+    ///
+    /// Heads are checked when the stream is read, so every other head
+    /// is a token of the stream.
+    fn head_span(&self, head: usize) -> Option<Span> {
+        match head {
+            0 => None,
+            head => Some(self.tokens[head - 1].span),
+        }
+    }
+}
+
+/// Segmentation and attachment counts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SegmentationCounts {
+    /// Number of gold sentences.
+    pub gold_sentences: usize,
+
+    /// Number of predicted sentences.
+    pub predicted_sentences: usize,
+
+    /// Number of predicted sentences that span exactly the characters of
+    /// a gold sentence.
+    pub correct_sentences: usize,
+
+    /// Number of gold words.
+    pub gold_words: usize,
+
+    /// Number of predicted words.
+    pub predicted_words: usize,
+
+    /// Number of predicted words that span exactly the characters of a
+    /// gold word.
+    pub correct_words: usize,
+
+    /// Number of correct words with a correct head.
+    pub correct_head: usize,
+
+    /// Number of correct words with a correct head and relation.
+    pub correct_head_label: usize,
+}
+
+impl SegmentationCounts {
+    pub fn sentence_precision(&self) -> f32 {
+        ratio(self.correct_sentences, self.predicted_sentences)
+    }
+
+    pub fn sentence_recall(&self) -> f32 {
+        ratio(self.correct_sentences, self.gold_sentences)
+    }
+
+    pub fn sentence_f1(&self) -> f32 {
+        f_beta(self.sentence_precision(), self.sentence_recall(), 1.)
+    }
+
+    pub fn word_precision(&self) -> f32 {
+        ratio(self.correct_words, self.predicted_words)
+    }
+
+    pub fn word_recall(&self) -> f32 {
+        ratio(self.correct_words, self.gold_words)
+    }
+
+    pub fn word_f1(&self) -> f32 {
+        f_beta(self.word_precision(), self.word_recall(), 1.)
+    }
+
+    pub fn uas_precision(&self) -> f32 {
+        ratio(self.correct_head, self.predicted_words)
+    }
+
+    pub fn uas_recall(&self) -> f32 {
+        ratio(self.correct_head, self.gold_words)
+    }
+
+    pub fn uas_f1(&self) -> f32 {
+        f_beta(self.uas_precision(), self.uas_recall(), 1.)
+    }
+
+    pub fn las_precision(&self) -> f32 {
+        ratio(self.correct_head_label, self.predicted_words)
+    }
+
+    pub fn las_recall(&self) -> f32 {
+        ratio(self.correct_head_label, self.gold_words)
+    }
+
+    pub fn las_f1(&self) -> f32 {
+        f_beta(self.las_precision(), self.las_recall(), 1.)
+    }
+}
+
+/// Evaluate predictions that may be segmented into different sentences
+/// and words.
+///
+/// Both inputs must have the same characters, apart from whitespace.
+pub fn evaluate_segmentation(val_reader: impl ReadSentence, pred_reader: impl ReadSentence) -> Result<SegmentationCounts, Error> {
+    let val = TokenStream::read(val_reader)?;
+    let pred = TokenStream::read(pred_reader)?;
+    if let Some(offset) = val.text.iter().zip(&pred.text).position(|(val_char, pred_char)| val_char != pred_char) {
+        return Err(format_err!(
+            "Character {}: validation character '{}' differs from prediction character '{}'",
+            offset + 1,
+            val.text[offset],
+            pred.text[offset]
+        ));
+    }
+    if val.text.len() != pred.text.len() {
+        return Err(format_err!(
+            "Validation has {} characters, prediction has {} characters",
+            val.text.len(),
+            pred.text.len()
+        ));
+    }
+
+    let val_words = val
+        .tokens
+        .iter()
+        .map(|token| (token.span, token))
+        .collect::<HashMap<_, _>>();
+    let mut counts = SegmentationCounts {
+        gold_sentences: val.sentences.len(),
+        predicted_sentences: pred.sentences.len(),
+        correct_sentences: val.sentences.intersection(&pred.sentences).count(),
+        gold_words: val.tokens.len(),
+        predicted_words: pred.tokens.len(),
+        ..SegmentationCounts::default()
+    };
+    for pred_token in &pred.tokens {
+        let val_token = match val_words.get(&pred_token.span) {
+            Some(val_token) => val_token,
+            None => continue,
+        };
+        counts.correct_words += 1;
+        let head_correct = match (val_token.head, pred_token.head) {
+            (Some(val_head), Some(pred_head)) => val.head_span(val_head) == pred.head_span(pred_head),
+            _ => false,
+        };
+        if head_correct {
+            counts.correct_head += 1;
+            counts.correct_head_label += (pred_token.relation == val_token.relation) as usize;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate_segmentation;
+    use crate::conllu::Reader;
+
+    static GOLD: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tnsubj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+4\t.\t.\tPUNCT\t.\t_\t3\tpunct\t_\t_
+
+1\tCats\tcat\tNOUN\tNNS\t_\t2\tnsubj\t_\t_
+2\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    /// The period starts the second sentence.
+    static PRED: &str = "\
+1\tThe\tthe\tDET\tDT\t_\t2\tdet\t_\t_
+2\tdog\tdog\tNOUN\tNN\t_\t3\tnsubj\t_\t_
+3\tbarks\tbark\tVERB\tVBZ\t_\t0\troot\t_\t_
+
+1\t.\t.\tPUNCT\t.\t_\t0\tpunct\t_\t_
+2\tCats\tcat\tNOUN\tNNS\t_\t3\tnsubj\t_\t_
+3\tsleep\tsleep\tVERB\tVBP\t_\t0\troot\t_\t_
+";
+
+    #[test]
+    fn aligns_by_character_offsets() {
+        let counts = evaluate_segmentation(Reader::new(GOLD.as_bytes()), Reader::new(PRED.as_bytes())).unwrap();
+        assert_eq!((counts.gold_sentences, counts.predicted_sentences, counts.correct_sentences), (2, 2, 0));
+        assert_eq!((counts.gold_words, counts.predicted_words, counts.correct_words), (6, 6, 6));
+        // Only the head of the period is wrong.
+        assert_eq!((counts.correct_head, counts.correct_head_label), (5, 5));
+        assert_eq!(counts.sentence_f1(), 0.);
+        assert!((counts.las_f1() - 5. / 6.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn different_text_is_an_error() {
+        let pred = PRED.replace("\tCats\t", "\tDogs\t");
+        assert!(evaluate_segmentation(Reader::new(GOLD.as_bytes()), Reader::new(pred.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn empty_form_is_an_error() {
+        let empty = "1\t \t_\tX\tX\t_\t0\troot\t_\t_\n";
+        assert!(evaluate_segmentation(Reader::new(empty.as_bytes()), Reader::new(empty.as_bytes())).is_err());
+    }
+}
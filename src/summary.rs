@@ -0,0 +1,277 @@
+use std::io::Write;
+
+use failure::Error;
+use serde::Serialize;
+
+use crate::metrics::Rounding;
+
+/// Scalar evaluation metrics.
+///
+/// The field names are written as-is to the JSON output and are
+/// considered stable.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    /// Unlabeled attachment score.
+    pub uas: f32,
+
+    /// Labeled attachment score.
+    pub las: f32,
+
+    /// Label accuracy.
+    pub la: f32,
+
+    /// Undirected unlabeled attachment score.
+    pub uuas: f32,
+
+    /// Content-word labeled attachment score.
+    pub clas: f32,
+
+    /// LAS averaged over gold relations.
+    pub macro_las: f32,
+
+    /// Fraction of sentences with only correct heads.
+    pub uem: f32,
+
+    /// Fraction of sentences with only correct heads and relations.
+    pub lem: f32,
+
+    /// POS tag accuracy.
+    pub pos_accuracy: f32,
+
+    /// Lemma accuracy.
+    pub lemma_accuracy: f32,
+
+    /// Number of scored tokens.
+    pub tokens: usize,
+
+    /// Number of sentences.
+    pub sentences: usize,
+
+    /// Mean absolute difference of predicted and gold head distance.
+    pub mean_distance_error: f32,
+
+    /// Median absolute difference of predicted and gold head distance.
+    pub median_distance_error: f32,
+
+    /// Fraction of tokens with the gold head distance.
+    pub exact_distance: f32,
+
+    /// Root identification scores.
+    pub root: RootSummary,
+}
+
+/// Root identification scores, see `RootPrf`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RootSummary {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl Summary {
+    /// Write the summary as `key<TAB>value` lines.
+    ///
+    /// Scores are formatted as on standard output.
+    pub fn write_tsv(&self, mut w: impl Write, rounding: Rounding, precision: usize) -> Result<(), Error> {
+        writeln!(w, "uas\t{}", rounding.format(self.uas, precision))?;
+        writeln!(w, "las\t{}", rounding.format(self.las, precision))?;
+        writeln!(w, "la\t{}", rounding.format(self.la, precision))?;
+        writeln!(w, "uuas\t{}", rounding.format(self.uuas, precision))?;
+        writeln!(w, "clas\t{}", rounding.format(self.clas, precision))?;
+        writeln!(w, "macro_las\t{}", rounding.format(self.macro_las, precision))?;
+        writeln!(w, "uem\t{}", rounding.format(self.uem, precision))?;
+        writeln!(w, "lem\t{}", rounding.format(self.lem, precision))?;
+        writeln!(w, "pos_accuracy\t{}", rounding.format(self.pos_accuracy, precision))?;
+        writeln!(w, "lemma_accuracy\t{}", rounding.format(self.lemma_accuracy, precision))?;
+        writeln!(w, "tokens\t{}", self.tokens)?;
+        writeln!(w, "sentences\t{}", self.sentences)?;
+        writeln!(w, "mean_distance_error\t{}", rounding.format(self.mean_distance_error, precision))?;
+        writeln!(w, "median_distance_error\t{}", rounding.format(self.median_distance_error, precision))?;
+        writeln!(w, "exact_distance\t{}", rounding.format(self.exact_distance, precision))?;
+        writeln!(w, "root_precision\t{}", rounding.format(self.root.precision, precision))?;
+        writeln!(w, "root_recall\t{}", rounding.format(self.root.recall, precision))?;
+        writeln!(w, "root_f1\t{}", rounding.format(self.root.f1, precision))?;
+        Ok(())
+    }
+
+    /// The scores of the summary with their `write_tsv` keys.
+    pub fn scores(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("uas", self.uas),
+            ("las", self.las),
+            ("la", self.la),
+            ("uuas", self.uuas),
+            ("clas", self.clas),
+            ("macro_las", self.macro_las),
+            ("uem", self.uem),
+            ("lem", self.lem),
+            ("pos_accuracy", self.pos_accuracy),
+            ("lemma_accuracy", self.lemma_accuracy),
+            ("mean_distance_error", self.mean_distance_error),
+            ("median_distance_error", self.median_distance_error),
+            ("exact_distance", self.exact_distance),
+            ("root_precision", self.root.precision),
+            ("root_recall", self.root.recall),
+            ("root_f1", self.root.f1),
+        ]
+    }
+
+    /// Write the summary as a single JSON object.
+    ///
+    /// Scores are rounded as on standard output. Scores are 0 when no
+    /// token was scored, see `ratio`. Non-finite scores are written as
+    /// `null`.
+    pub fn write_json(&self, mut w: impl Write, rounding: Rounding, precision: usize) -> Result<(), Error> {
+        serde_json::to_writer(&mut w, &self.rounded(rounding, precision))?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    /// Round all scores to `precision` decimal places.
+    fn rounded(&self, rounding: Rounding, precision: usize) -> Summary {
+        let round = |value| rounding.apply(value, precision);
+        Summary {
+            uas: round(self.uas),
+            las: round(self.las),
+            la: round(self.la),
+            uuas: round(self.uuas),
+            clas: round(self.clas),
+            macro_las: round(self.macro_las),
+            uem: round(self.uem),
+            lem: round(self.lem),
+            pos_accuracy: round(self.pos_accuracy),
+            lemma_accuracy: round(self.lemma_accuracy),
+            mean_distance_error: round(self.mean_distance_error),
+            median_distance_error: round(self.median_distance_error),
+            exact_distance: round(self.exact_distance),
+            root: RootSummary {
+                precision: round(self.root.precision),
+                recall: round(self.root.recall),
+                f1: round(self.root.f1),
+            },
+            ..self.clone()
+        }
+    }
+}
+
+/// Mean and sample standard deviation of a score over several runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeanStd {
+    pub mean: f32,
+    pub std: f32,
+}
+
+/// Compute the mean and sample standard deviation of every score of the
+/// summaries of several runs, such as cross-validation folds or seeds.
+///
+/// The standard deviation of a single run is 0.
+pub fn run_statistics(summaries: &[Summary]) -> Vec<(&'static str, MeanStd)> {
+    let scores = summaries.iter().map(Summary::scores).collect::<Vec<_>>();
+    let n = scores.len() as f64;
+    let mut statistics = Vec::new();
+    for (idx, &(name, _)) in scores.first().into_iter().flatten().enumerate() {
+        let values = scores.iter().map(|run| run[idx].1 as f64).collect::<Vec<_>>();
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = if values.len() > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.)
+        } else {
+            0.
+        };
+        statistics.push((
+            name,
+            MeanStd {
+                mean: mean as f32,
+                std: variance.sqrt() as f32,
+            },
+        ));
+    }
+    statistics
+}
+
+/// Write the summaries of several predictions as a table.
+///
+/// Every row holds the name of a prediction, its scores in the order of
+/// `Summary::scores` and its number of tokens and sentences.
+pub fn write_summary_table(
+    mut w: impl Write,
+    summaries: &[(String, Summary)],
+    rounding: Rounding,
+    precision: usize,
+) -> Result<(), Error> {
+    let names = match summaries.first() {
+        Some((_, summary)) => summary.scores().into_iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        None => return Ok(()),
+    };
+    writeln!(w, "prediction\t{}\ttokens\tsentences", names.join("\t"))?;
+    for (name, summary) in summaries {
+        write!(w, "{}", name)?;
+        for (_, score) in summary.scores() {
+            write!(w, "\t{}", rounding.format(score, precision))?;
+        }
+        writeln!(w, "\t{}\t{}", summary.tokens, summary.sentences)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_statistics, RootSummary, Summary};
+    use crate::Rounding;
+
+    fn summary(uas: f32) -> Summary {
+        Summary {
+            uas,
+            las: 0.5,
+            la: 0.75,
+            uuas: 1.,
+            clas: 0.5,
+            macro_las: 0.5,
+            uem: 0.,
+            lem: 0.,
+            pos_accuracy: 1.,
+            lemma_accuracy: 0.75,
+            tokens: 4,
+            sentences: 1,
+            mean_distance_error: 0.25,
+            median_distance_error: 0.,
+            exact_distance: 0.75,
+            root: RootSummary {
+                precision: 1.,
+                recall: 1.,
+                f1: 1.,
+            },
+        }
+    }
+
+    #[test]
+    fn json_uses_rounding_and_precision() {
+        let mut json = Vec::new();
+        summary(2. / 3.).write_json(&mut json, Rounding::Trunc, 2).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"uas\":0.66,\"las\":0.5,\"la\":0.75,\"uuas\":1.0,\"clas\":0.5,\"macro_las\":0.5,\
+             \"uem\":0.0,\"lem\":0.0,\"pos_accuracy\":1.0,\"lemma_accuracy\":0.75,\"tokens\":4,\"sentences\":1,\
+             \"mean_distance_error\":0.25,\"median_distance_error\":0.0,\"exact_distance\":0.75,\
+             \"root\":{\"precision\":1.0,\"recall\":1.0,\"f1\":1.0}}\n"
+        );
+
+        let mut json = Vec::new();
+        summary(f32::NAN).write_json(&mut json, Rounding::HalfEven, 4).unwrap();
+        assert!(String::from_utf8(json).unwrap().starts_with("{\"uas\":null,"));
+    }
+
+    #[test]
+    fn statistics_over_runs() {
+        let statistics = run_statistics(&[summary(0.5), summary(0.7)]);
+        let (name, uas) = statistics[0];
+        assert_eq!(name, "uas");
+        assert!((uas.mean - 0.6).abs() < 1e-6);
+        assert!((uas.std - 0.02f32.sqrt()).abs() < 1e-6);
+        assert_eq!(run_statistics(&[summary(0.5)])[0].1.std, 0.);
+
+        let names = statistics.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+        for name in &["uuas", "clas", "macro_las", "uem", "lem", "pos_accuracy", "lemma_accuracy"] {
+            assert!(names.contains(name));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use conllx::token::Token;
+use failure::{format_err, Error};
+
+use crate::feats::parse_features;
+use crate::PosColumn;
+
+/// Per-token weights for weighted attachment scores.
+///
+/// Tokens without a weight have weight 1.
+#[derive(Clone, Debug)]
+pub enum TokenWeights {
+    /// Weights by form, e.g. from a frequency table.
+    Forms(HashMap<String, f32>),
+
+    /// Weights by gold relation, after relation rewriting.
+    Relations(HashMap<String, f32>),
+
+    /// Weights by gold POS tag of the POS accuracy tag column.
+    Tags(HashMap<String, f32>),
+
+    /// Weights from the numeric value of the named token feature.
+    Feature(String),
+}
+
+impl TokenWeights {
+    /// Read form weights from a file.
+    ///
+    /// Every line holds a form and its weight, separated by a tab. Empty
+    /// lines and lines starting with `#` are ignored.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        Ok(TokenWeights::Forms(read_weights(path, "FORM")?))
+    }
+
+    /// Read relation weights from a file.
+    ///
+    /// The file format is the same as for `from_file`, with relations
+    /// instead of forms.
+    pub fn relations_from_file(path: &str) -> Result<Self, Error> {
+        Ok(TokenWeights::Relations(read_weights(path, "REL")?))
+    }
+
+    /// Read POS tag weights from a file.
+    ///
+    /// The file format is the same as for `from_file`, with tags instead
+    /// of forms.
+    pub fn tags_from_file(path: &str) -> Result<Self, Error> {
+        Ok(TokenWeights::Tags(read_weights(path, "TAG")?))
+    }
+
+    /// Get the weight of a gold token with the relation `rel`.
+    pub fn weight(&self, token: &Token, rel: &str, pos_column: PosColumn) -> Result<f32, Error> {
+        match self {
+            TokenWeights::Forms(weights) => Ok(weights.get(token.form()).copied().unwrap_or(1.)),
+            TokenWeights::Relations(weights) => Ok(weights.get(rel).copied().unwrap_or(1.)),
+            TokenWeights::Tags(weights) => Ok(pos_column
+                .tag(token)
+                .and_then(|tag| weights.get(tag))
+                .copied()
+                .unwrap_or(1.)),
+            TokenWeights::Feature(name) => {
+                let features = match token.features() {
+                    Some(features) => features.as_str(),
+                    None => return Ok(1.),
+                };
+                match parse_features(features).get(name.as_str()) {
+                    Some(value) => value
+                        .parse::<f32>()
+                        .map_err(|_| format_err!("Cannot parse weight feature {} of token '{}': {}", name, token.form(), value)),
+                    None => Ok(1.),
+                }
+            }
+        }
+    }
+}
+
+/// Read `KEY<TAB>WEIGHT` lines, `key` is the name of the key column in
+/// error messages.
+fn read_weights(path: &str, key: &str) -> Result<HashMap<String, f32>, Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut weights = HashMap::new();
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue
+        }
+        let mut parts = line.split('\t');
+        let (value, weight) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(value), Some(weight), None) => (value, weight.trim()),
+            _ => return Err(format_err!("{}:{}: expected {}<TAB>WEIGHT", path, line_no + 1, key)),
+        };
+        let weight = weight
+            .parse::<f32>()
+            .map_err(|_| format_err!("{}:{}: cannot parse weight: {}", path, line_no + 1, weight))?;
+        weights.insert(value.to_owned(), weight);
+    }
+    Ok(weights)
+}